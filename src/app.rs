@@ -1,17 +1,40 @@
+mod launch;
+mod navigation;
+mod notifications;
+mod profiles;
+mod snapshot;
 mod views;
 
-use std::sync::{atomic::AtomicUsize, Arc};
+use std::{
+    collections::HashSet,
+    sync::{atomic::AtomicUsize, Arc},
+};
 
 use egui::{emath::TSTransform, Visuals};
 use reqwest::Client;
 
 use crate::{
-    graphs::RelationStorage,
+    bookmarks::{Bookmarks, BookmarksPanel},
+    console::{script::LogHost, History, Runner},
+    crawler::Crawler,
+    graphs::{
+        export::ExportPanel,
+        persist::{PersistedGraph, RELATIONS_KEY},
+        RelationStorage,
+    },
     handle::Handle,
-    http::{HttpContext, HttpLog, LogEntry},
+    http::{self, HttpContext, HttpLog, HttpMessage, RequestCache, Session, SessionPanel},
 };
 
-use self::views::View;
+pub use self::launch::LaunchOptions;
+
+use self::{
+    navigation::Navigation,
+    notifications::{Notifications, Severity},
+    profiles::ServerProfiles,
+    snapshot::{Snapshot, SnapshotPanel},
+    views::View,
+};
 
 pub struct App {
     state: State,
@@ -25,6 +48,12 @@ struct Storage {
     target: Handle,
     transform: TSTransform,
     view: View,
+    servers: ServerProfiles,
+    history: History,
+    /// The targets inspected before and after the current one.
+    navigation: Navigation,
+    /// Names for handles, written as `@name` wherever a handle is accepted.
+    bookmarks: Bookmarks,
 }
 
 impl Default for Storage {
@@ -36,43 +65,48 @@ impl Default for Storage {
             .unwrap(),
             transform: TSTransform::default(),
             view: View::Graph,
+            servers: ServerProfiles::default(),
+            history: History::default(),
+            navigation: Navigation::default(),
+            bookmarks: Bookmarks::default(),
         }
     }
 }
 
 struct State {
     target_input: String,
-    error: Error,
+    /// The target field was left last frame, so short handles and bookmarks
+    /// in it are resolved.
+    target_submitted: bool,
+    /// Why the target field could not be resolved.
+    target_error: String,
+    notifications: Notifications,
     first_render: bool,
     client: Arc<Client>,
     log: HttpLog,
     connections: RelationStorage,
     counter: Arc<AtomicUsize>,
-}
-
-#[derive(Default)]
-struct Error {
-    content: String,
-    dirty: bool,
-}
-
-impl Error {
-    fn write(&mut self, content: String) {
-        self.dirty = true;
-        self.content = content;
-    }
-
-    fn read(&self) -> &str {
-        if self.dirty {
-            &self.content
-        } else {
-            ""
-        }
-    }
-
-    fn clear(&mut self) {
-        self.dirty = false;
-    }
+    cache: Arc<RequestCache>,
+    /// Records or replays responses.
+    session: Arc<Session>,
+    session_panel: SessionPanel,
+    /// Nodes moved by hand, which keep their position when re-laid out.
+    pinned: HashSet<Handle>,
+    /// Moves every node that was not moved by hand on the next frame.
+    relayout: bool,
+    /// Nodes highlighted by clicking on a connection.
+    selected: HashSet<Handle>,
+    crawler: Crawler,
+    /// Runs the commands typed into the console.
+    script: Runner,
+    export: ExportPanel,
+    /// The opened snapshot, whose graph replaces the fetched one while open.
+    snapshot: Option<Snapshot>,
+    snapshot_panel: SnapshotPanel,
+    bookmarks_panel: BookmarksPanel,
+    /// The url fragment last read or written, to notice when it changes.
+    #[cfg(target_arch = "wasm32")]
+    fragment: String,
 }
 
 impl Default for State {
@@ -80,33 +114,120 @@ impl Default for State {
         Self {
             target_input: "1000000000000000000000000000000000000000000000000000000000000024"
                 .to_owned(),
-            error: Error::default(),
+            target_submitted: false,
+            target_error: String::new(),
+            notifications: Notifications::default(),
             first_render: true,
-            client: Arc::new(Client::new()),
+            client: Arc::new(http::client()),
             connections: RelationStorage::default(),
             counter: Arc::new(AtomicUsize::new(0)),
+            cache: Arc::new(RequestCache::default()),
+            session: Arc::new(Session::default()),
+            session_panel: SessionPanel::default(),
             log: HttpLog::new(),
+            relayout: false,
+            pinned: HashSet::new(),
+            selected: HashSet::new(),
+            crawler: Crawler::default(),
+            script: Runner::default(),
+            export: ExportPanel::default(),
+            snapshot: None,
+            snapshot_panel: SnapshotPanel::default(),
+            bookmarks_panel: BookmarksPanel::default(),
+            #[cfg(target_arch = "wasm32")]
+            fragment: String::new(),
         }
     }
 }
 
 impl App {
     /// Called once before the first frame.
-    pub fn new(cc: &eframe::CreationContext<'_>) -> Self {
+    pub fn new(cc: &eframe::CreationContext<'_>, options: LaunchOptions) -> Self {
         // Load previous app state (if any).
         // Note that you must enable the `persistence` feature for this to work.
-        if let Some(storage) = cc.storage {
-            return Self {
-                state: State::default(),
-                storage: eframe::get_value(storage, eframe::APP_KEY).unwrap_or_default(),
-            };
-        }
+        let mut app = match cc.storage {
+            Some(storage) => {
+                let connections = eframe::get_value::<PersistedGraph>(storage, RELATIONS_KEY)
+                    .map(RelationStorage::from_persisted)
+                    .unwrap_or_default();
+                Self {
+                    state: State {
+                        connections,
+                        ..State::default()
+                    },
+                    storage: eframe::get_value(storage, eframe::APP_KEY).unwrap_or_default(),
+                }
+            }
+            None => {
+                cc.egui_ctx.set_visuals(Visuals::dark());
+                App {
+                    state: State::default(),
+                    storage: Storage::default(),
+                }
+            }
+        };
+        app.state.target_input = app.storage.target.to_hex();
+        app.launch(&cc.egui_ctx, options);
+        app
+    }
 
-        cc.egui_ctx.set_visuals(Visuals::dark());
+    /// Shows what the options ask for.
+    fn launch(&mut self, ctx: &egui::Context, options: LaunchOptions) {
+        if let Some(server) = options.server {
+            self.storage.servers.select(server);
+        }
+        if let Some(text) = options.target {
+            let bookmarks = &self.storage.bookmarks;
+            match bookmarks.parse_handle(&text, &self.state.connections) {
+                Ok(target) => {
+                    self.state.target_input = target.to_hex();
+                    self.storage.target = target;
+                    self.state.relayout = true;
+                }
+                Err(e) => self.state.notifications.push(
+                    ctx,
+                    Severity::Warning,
+                    format!("Invalid target {text}: {e:#}"),
+                ),
+            }
+        }
+        if let Some(view) = options.view {
+            self.storage.view = view;
+        }
+    }
 
-        App {
-            state: State::default(),
-            storage: Storage::default(),
+    /// Applies the url fragment when it was changed from outside, such as by
+    /// following a link, and otherwise updates it to what is shown.
+    #[cfg(target_arch = "wasm32")]
+    fn sync_fragment(&mut self, ctx: &egui::Context) {
+        let Some(location) = web_sys::window().map(|w| w.location()) else {
+            return;
+        };
+        let Ok(fragment) = location.hash() else {
+            return;
+        };
+        if fragment != self.state.fragment {
+            match LaunchOptions::from_fragment(&fragment) {
+                Ok(options) => {
+                    self.launch(ctx, options);
+                    let target = self.storage.target.clone();
+                    self.storage.navigation.visit_fragment(target);
+                }
+                Err(e) => self.state.notifications.push(
+                    ctx,
+                    Severity::Warning,
+                    format!("Invalid link {fragment}: {e:#}"),
+                ),
+            }
+            self.state.fragment = fragment;
+            return;
+        }
+        let shown = LaunchOptions::fragment(&self.storage.target, self.storage.view);
+        if fragment.trim_start_matches('#') != shown {
+            // Setting the fragment adds to the browser's history.
+            if location.set_hash(&shown).is_ok() {
+                self.state.fragment = format!("#{shown}");
+            }
         }
     }
 }
@@ -114,56 +235,163 @@ impl App {
 impl eframe::App for App {
     /// Called by the frame work to save state before shutdown.
     fn save(&mut self, storage: &mut dyn eframe::Storage) {
+        // Snapshots are not kept between sessions, so the live graph is saved.
+        let (graph, target) = match &self.state.snapshot {
+            Some(snapshot) => (&snapshot.live_graph, snapshot.live_target.clone()),
+            None => (&self.state.connections, self.storage.target.clone()),
+        };
+        let shown = std::mem::replace(&mut self.storage.target, target);
         eframe::set_value(storage, eframe::APP_KEY, &self.storage);
+        eframe::set_value(
+            storage,
+            RELATIONS_KEY,
+            &graph.to_persisted(&self.storage.target),
+        );
+        self.storage.target = shown;
     }
 
     /// Called each time the UI needs repainting, which may be many times per second.
     /// Put your widgets into a `SidePanel`, `TopPanel`, `CentralPanel`, `Window` or `Area`.
     fn update(&mut self, ctx: &eframe::egui::Context, _frame: &mut eframe::Frame) {
+        #[cfg(target_arch = "wasm32")]
+        self.sync_fragment(ctx);
+
         let storage = &mut self.storage;
         let State {
-            error,
+            notifications,
             client,
             connections,
             counter,
+            cache,
+            session,
+            session_panel,
             log,
+            crawler,
+            script,
+            export,
+            snapshot,
+            snapshot_panel,
+            bookmarks_panel,
+            target_input,
+            relayout,
             ..
         } = &mut self.state;
 
         let http_ctx = HttpContext {
             client: client.clone(),
             egui_ctx: ctx.clone(),
-            url_base: "127.0.0.1:9090".to_owned(),
+            url_base: storage.servers.url_base(),
             tx: log.tx.clone(),
             counter: counter.clone(),
+            cache: cache.clone(),
+            session: session.clone(),
+            offline: snapshot.as_ref().map_or(false, Snapshot::is_offline),
         };
 
-        if let Ok(new_connections) = log.rx.try_recv() {
-            match new_connections {
-                Ok(new_connections) => {
-                    error.clear();
-                    for (i, entry) in new_connections {
-                        if let LogEntry::Response(c) = entry.clone() {
-                            connections.insert(c.clone());
+        while let Ok(message) = log.rx.try_recv() {
+            log.on_message(&message);
+            match message {
+                HttpMessage::Sent(i, command, request, _) => {
+                    notifications.on_sent(i, command, request.as_ref());
+                }
+                HttpMessage::Status(..) | HttpMessage::Merged(..) => {}
+                HttpMessage::Received(i, result, _) => {
+                    if let Ok(relations) = &result {
+                        for request in http::tag_requests(relations) {
+                            request.send_unless_known(http_ctx.clone(), connections);
                         }
-                        log.log.push((i, entry));
+                    }
+                    crawler.on_received(i, &result);
+                    script.on_received(i, &result);
+                    notifications.on_received(ctx, i, &result);
+                    for relation in result.into_iter().flatten() {
+                        connections.insert(relation);
                     }
                 }
-                Err(e) => error.write(format!("{:#}", e)),
             }
         }
+        crawler.pump(&http_ctx, connections);
+        let mut host = LogHost {
+            ctx: &http_ctx,
+            log,
+        };
+        script.pump(&mut host, &storage.bookmarks, connections);
 
+        let mut go_to = storage.navigation.handle_input(ctx);
         egui::TopBottomPanel::top("top_panel").show(ctx, |ui| {
             ui.horizontal(|ui| {
                 ui.visuals_mut().button_frame = false;
                 ui.selectable_value(&mut storage.view, View::Graph, View::Graph.name());
                 ui.selectable_value(&mut storage.view, View::Text, View::Text.name());
+                ui.separator();
+                notifications.button(ui);
+                ui.separator();
+                if ui
+                    .button("copy link")
+                    .on_hover_text("Copy a link to the target in this view")
+                    .clicked()
+                {
+                    let link = LaunchOptions::link(
+                        &storage.servers.url_base(),
+                        &storage.target,
+                        storage.view,
+                    );
+                    ui.output_mut(|o| o.copied_text = link);
+                }
             });
+            ui.horizontal(|ui| {
+                if let Some(target) = storage.navigation.ui(ui) {
+                    go_to = Some(target);
+                }
+            });
+            if let Some(open) = snapshot {
+                if open.banner(ui) {
+                    let open = snapshot.take().unwrap();
+                    *connections = open.live_graph;
+                    storage.target = open.live_target;
+                    *target_input = storage.target.to_hex();
+                    *relayout = true;
+                }
+            }
         });
 
         egui::SidePanel::left("left_panel").show(ctx, |ui| {
-            // TODO add some nice controls
+            storage.servers.ui(ui);
+            ui.separator();
+            crawler.ui(ui);
+            ui.separator();
+            session_panel.ui(ui, session);
+            ui.separator();
+            export.ui(ui, connections, &storage.target);
+            ui.separator();
+            if let Some((name, json)) = snapshot_panel.ui(ui) {
+                match Snapshot::open(snapshot, name.clone(), &json, connections, &storage.target) {
+                    Ok(root) => {
+                        if let Some(root) = root {
+                            storage.target = root;
+                            *target_input = storage.target.to_hex();
+                        }
+                        *relayout = true;
+                        notifications.push(ctx, Severity::Info, format!("Opened snapshot {name}"));
+                        snapshot_panel.set_status(String::new());
+                    }
+                    Err(e) => snapshot_panel.set_status(format!("{e:#}")),
+                }
+            }
+            ui.separator();
+            if let Some(target) = bookmarks_panel.ui(ui, &mut storage.bookmarks, &storage.target) {
+                go_to = Some(target);
+            }
         });
+        if let Some(target) = go_to {
+            *target_input = target.to_hex();
+            storage.target = target;
+            *relayout = true;
+        }
+
+        if let Some(handle) = notifications.show(ctx) {
+            *target_input = handle.to_hex();
+        }
 
         egui::CentralPanel::default().show(ctx, |ui| {
             storage
@@ -172,5 +400,10 @@ impl eframe::App for App {
                 .draw(ui, &mut self.state, storage, &http_ctx);
             self.state.first_render = false;
         });
+
+        // Targets entered other than by following a relation.
+        if storage.navigation.current() != Some(&storage.target) {
+            storage.navigation.visit(storage.target.clone(), vec![]);
+        }
     }
 }
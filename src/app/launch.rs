@@ -0,0 +1,178 @@
+use anyhow::{bail, ensure, Context, Result};
+
+use crate::handle::Handle;
+
+use super::{profiles::ServerProfile, views::View};
+
+/// What the viewer shows when it opens, from the command line natively or
+/// from the url fragment on the web, like `#target=<handle>&view=graph`.
+#[derive(Default)]
+pub struct LaunchOptions {
+    pub(crate) server: Option<ServerProfile>,
+    /// The handle's hex, or `@name` for a bookmark, which is looked up once
+    /// the bookmarks are loaded.
+    pub(crate) target: Option<String>,
+    pub(crate) view: Option<View>,
+}
+
+impl LaunchOptions {
+    pub const USAGE: &'static str = "\
+Usage: fix_viewer [options] [link]
+
+Opens a link copied from the viewer, like
+http://127.0.0.1:9090/#target=<handle>&view=graph, and then applies the options.
+
+Options:
+  --server <url>     The server to send requests to, which is added to the profiles
+  --target <handle>  The handle to inspect, or @name for a bookmark
+  --view graph|text  The view to open
+  -h, --help         Print this message";
+
+    /// Parses the arguments after the program name, or returns `None` if help
+    /// was asked for.
+    pub fn from_args(args: impl IntoIterator<Item = String>) -> Result<Option<Self>> {
+        let mut options = Self::default();
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            if arg == "-h" || arg == "--help" {
+                return Ok(None);
+            }
+            let Some(key) = arg.strip_prefix("--") else {
+                options.set_link(&arg)?;
+                continue;
+            };
+            let value = args
+                .next()
+                .with_context(|| format!("{arg} expects a value"))?;
+            options.set(key, &value)?;
+        }
+        Ok(Some(options))
+    }
+
+    /// Parses a url fragment, with or without the leading `#`. Words without
+    /// a value, like the `#dev` used during development, are ignored.
+    #[cfg(any(target_arch = "wasm32", test))]
+    pub(crate) fn from_fragment(fragment: &str) -> Result<Self> {
+        let mut options = Self::default();
+        options.set_fragment(fragment)?;
+        Ok(options)
+    }
+
+    /// The url fragment showing `target` in `view`, without the leading `#`.
+    pub(crate) fn fragment(target: &Handle, view: View) -> String {
+        format!(
+            "target={}&view={}",
+            target.to_hex(),
+            view.name().to_lowercase()
+        )
+    }
+
+    /// A link to `target` in `view` on the web viewer served by the server.
+    pub(crate) fn link(url_base: &str, target: &Handle, view: View) -> String {
+        format!("{url_base}/#{}", Self::fragment(target, view))
+    }
+
+    /// Reads a link made by [`Self::link`].
+    fn set_link(&mut self, link: &str) -> Result<()> {
+        let (server, fragment) = link.split_once('#').unwrap_or((link, ""));
+        self.server = Some(ServerProfile::from_url(server)?);
+        self.set_fragment(fragment)
+    }
+
+    /// Reads the target and view from a url fragment. Fragments can't
+    /// change the server, so that opening a link can't point the viewer at
+    /// another host and keep it as a profile.
+    fn set_fragment(&mut self, fragment: &str) -> Result<()> {
+        let fragment = fragment.trim_start_matches('#');
+        for (key, value) in fragment.split('&').filter_map(|pair| pair.split_once('=')) {
+            ensure!(
+                matches!(key, "target" | "view"),
+                "links can only set the target and view, not {key}"
+            );
+            self.set(key, value)?;
+        }
+        Ok(())
+    }
+
+    fn set(&mut self, key: &str, value: &str) -> Result<()> {
+        match key {
+            "server" => self.server = Some(ServerProfile::from_url(value)?),
+            "target" => {
+                let target = match value.strip_prefix('@') {
+                    Some(_) => value.to_owned(),
+                    None => Handle::from_hex(value).context("invalid target")?.to_hex(),
+                };
+                self.target = Some(target);
+            }
+            "view" => {
+                self.view = Some(match value {
+                    "graph" => View::Graph,
+                    "text" => View::Text,
+                    _ => bail!("unknown view {value}, expected graph or text"),
+                })
+            }
+            _ => bail!("unknown option {key}"),
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::handle::test_handle;
+
+    fn args(args: &str) -> Vec<String> {
+        args.split_whitespace().map(str::to_owned).collect()
+    }
+
+    #[test]
+    fn parses_args() {
+        let hex = "ab".repeat(32);
+        let options = LaunchOptions::from_args(args(&format!(
+            "--server 127.0.0.1:8080 --target {hex} --view text"
+        )))
+        .unwrap()
+        .unwrap();
+        assert_eq!(options.server.unwrap().url_base(), "http://127.0.0.1:8080");
+        assert_eq!(options.target.unwrap(), hex);
+        assert!(options.view == Some(View::Text));
+        assert!(LaunchOptions::from_args(args("--help")).unwrap().is_none());
+        assert!(LaunchOptions::from_args(args("--view")).is_err());
+        assert!(LaunchOptions::from_args(args("--view tree")).is_err());
+        assert!(LaunchOptions::from_args(args("--target")).is_err());
+    }
+
+    #[test]
+    fn opens_links() {
+        let target = test_handle(7);
+        let link = LaunchOptions::link("http://fix.example:9090", &target, View::Text);
+        let options = LaunchOptions::from_args(args(&format!("{link} --view graph")))
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            options.server.unwrap().url_base(),
+            "http://fix.example:9090"
+        );
+        assert_eq!(options.target, Some(target.to_hex()));
+        assert!(options.view == Some(View::Graph));
+        let options = LaunchOptions::from_args(args("--target @add-simple")).unwrap();
+        assert_eq!(options.unwrap().target.as_deref(), Some("@add-simple"));
+    }
+
+    #[test]
+    fn round_trips_fragments() {
+        let target = test_handle(7);
+        let fragment = LaunchOptions::fragment(&target, View::Graph);
+        let options = LaunchOptions::from_fragment(&format!("#{fragment}")).unwrap();
+        assert_eq!(options.target, Some(target.to_hex()));
+        assert!(options.view == Some(View::Graph));
+        assert!(options.server.is_none());
+        assert!(LaunchOptions::from_fragment("#dev")
+            .unwrap()
+            .target
+            .is_none());
+        assert!(LaunchOptions::from_fragment("#target=ab").is_err());
+        assert!(LaunchOptions::from_fragment("#server=http://fix.example:9090").is_err());
+    }
+}
@@ -0,0 +1,225 @@
+use egui::{Context, Key, Modifiers, RichText, Ui};
+
+use crate::{graphs::Relation, handle::Handle};
+
+/// The most targets kept.
+const MAX_STEPS: usize = 200;
+
+/// The targets that were inspected, which can be gone back and forward
+/// through like in a browser.
+#[derive(Default, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub(crate) struct Navigation {
+    steps: Vec<Step>,
+    /// The index of the current target.
+    position: usize,
+}
+
+/// A target, and how it was reached from the one before it.
+#[derive(Clone, serde::Deserialize, serde::Serialize)]
+struct Step {
+    target: Handle,
+    /// The relations followed from the previous target, which is empty if
+    /// the target was entered some other way.
+    via: Vec<Relation>,
+}
+
+impl Navigation {
+    pub(crate) fn current(&self) -> Option<&Handle> {
+        self.steps.get(self.position).map(|step| &step.target)
+    }
+
+    /// Makes `target` the current target, dropping the targets that could be
+    /// gone forward to.
+    pub(crate) fn visit(&mut self, target: Handle, via: Vec<Relation>) {
+        if self.current() == Some(&target) {
+            return;
+        }
+        self.steps.truncate(self.position + 1);
+        self.steps.push(Step { target, via });
+        let excess = self.steps.len().saturating_sub(MAX_STEPS);
+        self.steps.drain(..excess);
+        self.position = self.steps.len() - 1;
+    }
+
+    /// Makes the target of a changed url fragment current. The browser goes
+    /// back and forward through fragments itself, so reaching the previous
+    /// or next target goes back or forward instead of visiting it.
+    #[cfg(any(target_arch = "wasm32", test))]
+    pub(crate) fn visit_fragment(&mut self, target: Handle) {
+        if self.position > 0 && self.steps[self.position - 1].target == target {
+            self.position -= 1;
+        } else if self.steps.get(self.position + 1).map(|s| &s.target) == Some(&target) {
+            self.position += 1;
+        } else {
+            self.visit(target, vec![]);
+        }
+    }
+
+    pub(crate) fn back(&mut self) -> Option<Handle> {
+        self.go_to(self.position.checked_sub(1)?)
+    }
+
+    pub(crate) fn forward(&mut self) -> Option<Handle> {
+        self.go_to(self.position + 1)
+    }
+
+    fn go_to(&mut self, position: usize) -> Option<Handle> {
+        let target = self.steps.get(position)?.target.clone();
+        self.position = position;
+        Some(target)
+    }
+
+    /// The steps that led to the current target by following relations,
+    /// starting from the last target entered some other way.
+    fn trail(&self) -> &[Step] {
+        // Old state may not uphold the invariants.
+        if self.position >= self.steps.len() {
+            return &[];
+        }
+        let start = self.steps[..=self.position]
+            .iter()
+            .rposition(|step| step.via.is_empty())
+            .unwrap_or(0);
+        &self.steps[start..=self.position]
+    }
+
+    /// Goes back or forward with Alt+Left and Alt+Right, or with the mouse's
+    /// back and forward buttons natively. Browsers handle those buttons
+    /// themselves, by going through the url fragments.
+    pub(crate) fn handle_input(&mut self, ctx: &Context) -> Option<Handle> {
+        let typing = ctx.memory(|m| m.focused().is_some());
+        let (back, forward) = ctx.input_mut(|i| {
+            #[cfg(not(target_arch = "wasm32"))]
+            let mouse = (
+                i.pointer.button_pressed(egui::PointerButton::Extra1),
+                i.pointer.button_pressed(egui::PointerButton::Extra2),
+            );
+            #[cfg(target_arch = "wasm32")]
+            let mouse = (false, false);
+            (
+                mouse.0 || !typing && i.consume_key(Modifiers::ALT, Key::ArrowLeft),
+                mouse.1 || !typing && i.consume_key(Modifiers::ALT, Key::ArrowRight),
+            )
+        });
+        match (back, forward) {
+            (true, _) => self.back(),
+            (_, true) => self.forward(),
+            _ => None,
+        }
+    }
+
+    /// Shows back and forward buttons and the trail to the current target,
+    /// returning a target to go to if one was clicked.
+    pub(crate) fn ui(&mut self, ui: &mut Ui) -> Option<Handle> {
+        let mut go_to = None;
+        let can_go_forward = self.position + 1 < self.steps.len();
+        if ui
+            .add_enabled(self.position > 0, egui::Button::new("⏴"))
+            .on_hover_text("Back (Alt+Left)")
+            .clicked()
+        {
+            go_to = self.back();
+        }
+        if ui
+            .add_enabled(can_go_forward, egui::Button::new("⏵"))
+            .on_hover_text("Forward (Alt+Right)")
+            .clicked()
+        {
+            go_to = self.forward();
+        }
+
+        let trail = self.trail();
+        let start = self.position + 1 - trail.len();
+        let mut clicked = None;
+        let mut previous: Option<&Handle> = None;
+        for (i, step) in trail.iter().enumerate() {
+            let mut from = previous.cloned();
+            for relation in &step.via {
+                let forward = from.as_ref() == Some(&relation.lhs);
+                let to = match relation.rhs.get_port_type() {
+                    Some((_, rhs)) if forward => rhs,
+                    _ => relation.lhs.clone(),
+                };
+                let label = relation.rhs.get_abbrev();
+                if forward {
+                    ui.weak(format!("— {label} →"));
+                } else {
+                    ui.weak(format!("← {label} —"));
+                }
+                // The last handle is the step's target, which is shown below.
+                if to != step.target {
+                    ui.label(RichText::new(to.short_hex()).monospace())
+                        .on_hover_text(to.to_hex());
+                }
+                from = Some(to);
+            }
+            let current = start + i == self.position;
+            let text = RichText::new(step.target.short_hex()).monospace();
+            if ui
+                .selectable_label(current, text)
+                .on_hover_text(step.target.to_hex())
+                .clicked()
+                && !current
+            {
+                clicked = Some(start + i);
+            }
+            previous = Some(&step.target);
+        }
+        if let Some(position) = clicked {
+            go_to = self.go_to(position);
+        }
+        go_to
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{graphs::RelationRhs, handle::test_handle};
+
+    fn targets(navigation: &Navigation) -> Vec<u8> {
+        navigation
+            .steps
+            .iter()
+            .map(|s| s.target.content[0])
+            .collect()
+    }
+
+    #[test]
+    fn goes_back_and_forward() {
+        let mut navigation = Navigation::default();
+        for i in 1..=3 {
+            navigation.visit(test_handle(i), vec![]);
+        }
+        assert_eq!(navigation.back(), Some(test_handle(2)));
+        assert_eq!(navigation.back(), Some(test_handle(1)));
+        assert_eq!(navigation.back(), None);
+        assert_eq!(navigation.forward(), Some(test_handle(2)));
+        // A fragment with the next target goes forward to it.
+        navigation.visit_fragment(test_handle(3));
+        assert_eq!(navigation.position, 2);
+        navigation.visit_fragment(test_handle(2));
+        assert_eq!(navigation.position, 1);
+        navigation.visit(test_handle(4), vec![]);
+        assert_eq!(targets(&navigation), [1, 2, 4]);
+        assert_eq!(navigation.forward(), None);
+        // Inspecting the previous target is a new step.
+        navigation.visit(test_handle(2), vec![]);
+        assert_eq!(targets(&navigation), [1, 2, 4, 2]);
+    }
+
+    #[test]
+    fn trails_followed_relations() {
+        let mut navigation = Navigation::default();
+        assert!(navigation.trail().is_empty());
+        navigation.visit(test_handle(1), vec![]);
+        navigation.visit(test_handle(2), vec![]);
+        let eval = Relation::new(test_handle(2), RelationRhs::Eval(test_handle(3)));
+        navigation.visit(test_handle(3), vec![eval]);
+        let trail: Vec<_> = navigation.trail().iter().map(|s| &s.target).collect();
+        assert_eq!(trail, [&test_handle(2), &test_handle(3)]);
+        navigation.back();
+        assert_eq!(navigation.trail().len(), 1);
+    }
+}
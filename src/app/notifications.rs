@@ -0,0 +1,286 @@
+use std::{collections::HashMap, time::Duration};
+
+use egui::{Align2, Color32, Context, Id, RichText, Ui};
+
+use crate::{
+    clock,
+    graphs::Relation,
+    handle::Handle,
+    http::{ErrorKind, HttpError, Request},
+};
+
+/// How long toasts are shown for.
+const TOAST_SECONDS: f64 = 6.0;
+
+/// The most toasts shown at once.
+const MAX_TOASTS: usize = 4;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Severity {
+    Info,
+    Warning,
+    Error,
+}
+
+pub(crate) struct Notification {
+    /// Seconds since the Unix epoch.
+    pub time: f64,
+    pub severity: Severity,
+    pub message: String,
+    /// The id and command of the request that caused the notification.
+    pub request: Option<(usize, String)>,
+    pub handle: Option<Handle>,
+    /// Whether the toast was closed before it expired.
+    toast_closed: bool,
+}
+
+/// Notifications about failed requests and other events, shown as toasts
+/// and in a window listing all of them.
+#[derive(Default)]
+pub(crate) struct Notifications {
+    entries: Vec<Notification>,
+    /// Commands of requests in flight, and the handles they are about, to
+    /// describe them if they fail.
+    pending: HashMap<usize, (String, Option<Handle>)>,
+    open: bool,
+    /// Only shows notifications about this handle in the window.
+    filter: Option<Handle>,
+}
+
+impl Severity {
+    fn color(self, ui: &Ui) -> Color32 {
+        match self {
+            Severity::Info => ui.visuals().text_color(),
+            Severity::Warning => ui.visuals().warn_fg_color,
+            Severity::Error => ui.visuals().error_fg_color,
+        }
+    }
+
+    fn icon(self) -> &'static str {
+        match self {
+            Severity::Info => "ℹ",
+            Severity::Warning => "⚠",
+            Severity::Error => "❌",
+        }
+    }
+}
+
+impl Notifications {
+    pub(crate) fn push(&mut self, ctx: &Context, severity: Severity, message: String) {
+        self.push_entry(ctx, severity, message, None, None);
+    }
+
+    fn push_entry(
+        &mut self,
+        ctx: &Context,
+        severity: Severity,
+        message: String,
+        request: Option<(usize, String)>,
+        handle: Option<Handle>,
+    ) {
+        self.entries.push(Notification {
+            time: clock::now(),
+            severity,
+            message,
+            request,
+            handle,
+            toast_closed: false,
+        });
+        ctx.request_repaint();
+    }
+
+    pub(crate) fn on_sent(&mut self, id: usize, command: String, request: Option<&Request>) {
+        let handle = request.map(|r| r.handle().clone());
+        self.pending.insert(id, (command, handle));
+    }
+
+    /// Notifies about a request if it failed.
+    pub(crate) fn on_received(
+        &mut self,
+        ctx: &Context,
+        id: usize,
+        result: &Result<Vec<Relation>, HttpError>,
+    ) {
+        let pending = self.pending.remove(&id);
+        let Err(error) = result else {
+            return;
+        };
+        let (request, handle) = match pending {
+            Some((command, handle)) => (Some((id, command)), handle),
+            None => (None, None),
+        };
+        // Fetching is disabled on purpose while viewing a snapshot.
+        let severity = match error.kind {
+            ErrorKind::Offline => Severity::Warning,
+            _ => Severity::Error,
+        };
+        self.push_entry(ctx, severity, error.kind.to_string(), request, handle);
+    }
+
+    /// The number of notifications about `handle`.
+    pub(crate) fn count_for(&self, handle: &Handle) -> usize {
+        self.entries
+            .iter()
+            .filter(|n| n.handle.as_ref() == Some(handle))
+            .count()
+    }
+
+    /// Opens the window showing only notifications about `handle`.
+    pub(crate) fn show_for(&mut self, handle: Handle) {
+        self.open = true;
+        self.filter = Some(handle);
+    }
+
+    /// A button opening the window, for the top panel.
+    pub(crate) fn button(&mut self, ui: &mut Ui) {
+        let text = match self.entries.len() {
+            0 => "notifications".to_owned(),
+            n => format!("notifications ({n})"),
+        };
+        if ui.selectable_label(self.open, text).clicked() {
+            self.open = !self.open;
+            self.filter = None;
+        }
+    }
+
+    /// Shows toasts and the window, returning a handle to go to if one was
+    /// clicked.
+    pub(crate) fn show(&mut self, ctx: &Context) -> Option<Handle> {
+        let mut go_to = None;
+        self.toasts(ctx);
+
+        let mut open = self.open;
+        egui::Window::new("Notifications")
+            .open(&mut open)
+            .default_width(420.0)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    if let Some(filter) = &self.filter {
+                        ui.label(format!("About {}", filter.to_hex()));
+                        if ui.button("show all").clicked() {
+                            self.filter = None;
+                        }
+                    }
+                    if ui.button("dismiss all").clicked() {
+                        let filter = self.filter.clone();
+                        self.entries
+                            .retain(|n| filter.is_some() && n.handle != filter);
+                    }
+                });
+                ui.separator();
+                let mut dismissed = None;
+                egui::ScrollArea::vertical().show(ui, |ui| {
+                    for (i, notification) in self.entries.iter().enumerate().rev() {
+                        if self.filter.is_some() && notification.handle != self.filter {
+                            continue;
+                        }
+                        ui.horizontal(|ui| {
+                            if ui.small_button("×").on_hover_text("Dismiss").clicked() {
+                                dismissed = Some(i);
+                            }
+                            ui.label(clock::time_of_day(notification.time))
+                                .on_hover_text(format!("Time of day in {}", clock::TIME_ZONE));
+                            notification.label(ui);
+                        });
+                        if let Some((id, command)) = &notification.request {
+                            ui.monospace(format!("[{id}]: > {command}"));
+                        }
+                        if let Some(handle) = &notification.handle {
+                            if ui
+                                .link(handle.to_hex())
+                                .on_hover_text("Show this handle")
+                                .clicked()
+                            {
+                                go_to = Some(handle.clone());
+                            }
+                        }
+                        ui.separator();
+                    }
+                });
+                if let Some(i) = dismissed {
+                    self.entries.remove(i);
+                }
+            });
+        self.open = open;
+        go_to
+    }
+
+    fn toasts(&mut self, ctx: &Context) {
+        let now = clock::now();
+        let mut shown: Vec<&mut Notification> = self
+            .entries
+            .iter_mut()
+            .rev()
+            .filter(|n| !n.toast_closed && now - n.time < TOAST_SECONDS)
+            .take(MAX_TOASTS)
+            .collect();
+        let Some(oldest) = shown.last() else {
+            return;
+        };
+        let expires_in = TOAST_SECONDS - (now - oldest.time);
+        ctx.request_repaint_after(Duration::from_secs_f64(expires_in.max(0.0)));
+
+        let mut open_window = false;
+        egui::Area::new(Id::new("toasts"))
+            .anchor(Align2::RIGHT_BOTTOM, [-8.0, -8.0])
+            .order(egui::Order::Tooltip)
+            .show(ctx, |ui| {
+                ui.set_max_width(360.0);
+                for notification in shown.iter_mut().rev() {
+                    egui::Frame::popup(ui.style()).show(ui, |ui| {
+                        ui.horizontal(|ui| {
+                            if ui.small_button("×").clicked() {
+                                notification.toast_closed = true;
+                            }
+                            if notification.label(ui).clicked() {
+                                open_window = true;
+                            }
+                        });
+                    });
+                }
+            });
+        if open_window {
+            self.open = true;
+            self.filter = None;
+        }
+    }
+}
+
+impl Notification {
+    fn label(&self, ui: &mut Ui) -> egui::Response {
+        let text = format!("{} {}", self.severity.icon(), self.message);
+        ui.add(
+            egui::Label::new(RichText::new(text).color(self.severity.color(ui)))
+                .wrap(true)
+                .sense(egui::Sense::click()),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::handle::test_handle;
+
+    #[test]
+    fn links_failures_to_handles() {
+        let ctx = Context::default();
+        let mut notifications = Notifications::default();
+        let handle = test_handle(1);
+        let command = format!("description {}", handle.to_hex());
+        let request = Request::from_cli(&command).unwrap();
+        notifications.on_sent(0, command.clone(), Some(&request));
+        notifications.on_sent(1, command.clone(), Some(&request));
+        notifications.on_received(&ctx, 0, &Ok(vec![]));
+        let error = HttpError {
+            id: 1,
+            kind: ErrorKind::Timeout,
+        };
+        notifications.on_received(&ctx, 1, &Err(error));
+        assert_eq!(notifications.count_for(&handle), 1);
+        let notification = &notifications.entries[0];
+        assert!(notification.severity == Severity::Error);
+        assert_eq!(notification.request, Some((1, command)));
+        assert!(notifications.pending.is_empty());
+    }
+}
@@ -0,0 +1,247 @@
+use anyhow::{bail, Context, Result};
+use egui::{ComboBox, DragValue, Grid, Ui};
+
+/// A named Fixpoint server that requests can be sent to.
+#[derive(serde::Deserialize, serde::Serialize, Clone, PartialEq, Debug)]
+#[serde(default)]
+pub(crate) struct ServerProfile {
+    pub name: String,
+    pub scheme: Scheme,
+    pub host: String,
+    pub port: u16,
+    pub base_path: String,
+}
+
+#[derive(serde::Deserialize, serde::Serialize, Clone, Copy, PartialEq, Debug)]
+pub(crate) enum Scheme {
+    Http,
+    Https,
+}
+
+impl Scheme {
+    fn name(&self) -> &'static str {
+        match self {
+            Scheme::Http => "http",
+            Scheme::Https => "https",
+        }
+    }
+
+    fn default_port(&self) -> u16 {
+        match self {
+            Scheme::Http => 80,
+            Scheme::Https => 443,
+        }
+    }
+}
+
+impl ServerProfile {
+    /// The url that request paths are appended to, without a trailing slash.
+    pub(crate) fn url_base(&self) -> String {
+        let base_path = self.base_path.trim_matches('/');
+        let mut url = format!(
+            "{}://{}:{}",
+            self.scheme.name(),
+            self.host.trim(),
+            self.port
+        );
+        if !base_path.is_empty() {
+            url.push('/');
+            url.push_str(base_path);
+        }
+        url
+    }
+
+    /// A profile for a url such as `http://host:port/base`, where the scheme
+    /// defaults to http.
+    pub(crate) fn from_url(url: &str) -> Result<Self> {
+        let full = if url.contains("://") {
+            url.to_owned()
+        } else {
+            format!("http://{url}")
+        };
+        let parsed =
+            reqwest::Url::parse(&full).with_context(|| format!("invalid server url {url}"))?;
+        let scheme = match parsed.scheme() {
+            "http" => Scheme::Http,
+            "https" => Scheme::Https,
+            other => bail!("unsupported scheme {other} in server url {url}"),
+        };
+        let host = parsed
+            .host_str()
+            .with_context(|| format!("missing host in server url {url}"))?;
+        let port = parsed.port().unwrap_or_else(|| scheme.default_port());
+        Ok(Self {
+            name: format!("{host}:{port}"),
+            scheme,
+            host: host.to_owned(),
+            port,
+            base_path: parsed.path().trim_matches('/').to_owned(),
+        })
+    }
+
+    /// The server the viewer itself was served from.
+    #[cfg(target_arch = "wasm32")]
+    fn from_location() -> Option<Self> {
+        let location = web_sys::window()?.location();
+        let scheme = match location.protocol().ok()?.as_str() {
+            "https:" => Scheme::Https,
+            _ => Scheme::Http,
+        };
+        let port = match location.port().ok()?.as_str() {
+            "" => scheme.default_port(),
+            port => port.parse().ok()?,
+        };
+        Some(Self {
+            name: "origin".to_owned(),
+            scheme,
+            host: location.hostname().ok()?,
+            port,
+            base_path: String::new(),
+        })
+    }
+}
+
+impl Default for ServerProfile {
+    fn default() -> Self {
+        #[cfg(target_arch = "wasm32")]
+        if let Some(profile) = Self::from_location() {
+            return profile;
+        }
+        Self {
+            name: "local".to_owned(),
+            scheme: Scheme::Http,
+            host: "127.0.0.1".to_owned(),
+            port: 9090,
+            base_path: String::new(),
+        }
+    }
+}
+
+/// The saved server profiles, of which exactly one is active.
+#[derive(serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub(crate) struct ServerProfiles {
+    profiles: Vec<ServerProfile>,
+    active: usize,
+}
+
+impl Default for ServerProfiles {
+    fn default() -> Self {
+        Self {
+            profiles: vec![ServerProfile::default()],
+            active: 0,
+        }
+    }
+}
+
+impl ServerProfiles {
+    /// The url base of the active profile.
+    pub(crate) fn url_base(&self) -> String {
+        // Old state may not uphold the invariants, so fall back to the default.
+        self.profiles
+            .get(self.active)
+            .map(ServerProfile::url_base)
+            .unwrap_or_else(|| ServerProfile::default().url_base())
+    }
+
+    /// Makes the profile with the same url active, adding it if there is none.
+    pub(crate) fn select(&mut self, profile: ServerProfile) {
+        let url_base = profile.url_base();
+        match self.profiles.iter().position(|p| p.url_base() == url_base) {
+            Some(i) => self.active = i,
+            None => {
+                self.profiles.push(profile);
+                self.active = self.profiles.len() - 1;
+            }
+        }
+    }
+
+    pub(crate) fn ui(&mut self, ui: &mut Ui) {
+        if self.profiles.is_empty() {
+            self.profiles.push(ServerProfile::default());
+        }
+        self.active = self.active.min(self.profiles.len() - 1);
+
+        ui.heading("Server");
+        ui.horizontal(|ui| {
+            ComboBox::from_id_source("server profile")
+                .selected_text(self.profiles[self.active].name.clone())
+                .show_ui(ui, |ui| {
+                    for (i, profile) in self.profiles.iter().enumerate() {
+                        ui.selectable_value(&mut self.active, i, &profile.name);
+                    }
+                });
+            if ui.button("+").on_hover_text("Add profile").clicked() {
+                let mut profile = self.profiles[self.active].clone();
+                profile.name = format!("profile {}", self.profiles.len());
+                self.profiles.push(profile);
+                self.active = self.profiles.len() - 1;
+            }
+            if ui
+                .add_enabled(self.profiles.len() > 1, egui::Button::new("-"))
+                .on_hover_text("Remove profile")
+                .clicked()
+            {
+                self.profiles.remove(self.active);
+                self.active = self.active.saturating_sub(1);
+            }
+        });
+
+        let profile = &mut self.profiles[self.active];
+        Grid::new("server profile fields")
+            .num_columns(2)
+            .show(ui, |ui| {
+                ui.label("Name:");
+                ui.text_edit_singleline(&mut profile.name);
+                ui.end_row();
+                ui.label("Scheme:");
+                ui.horizontal(|ui| {
+                    for scheme in [Scheme::Http, Scheme::Https] {
+                        let previous = profile.scheme;
+                        if ui
+                            .selectable_value(&mut profile.scheme, scheme, scheme.name())
+                            .changed()
+                            && profile.port == previous.default_port()
+                        {
+                            profile.port = scheme.default_port();
+                        }
+                    }
+                });
+                ui.end_row();
+                ui.label("Host:");
+                ui.text_edit_singleline(&mut profile.host);
+                ui.end_row();
+                ui.label("Port:");
+                ui.add(DragValue::new(&mut profile.port));
+                ui.end_row();
+                ui.label("Base path:");
+                ui.text_edit_singleline(&mut profile.base_path);
+                ui.end_row();
+            });
+        ui.small(profile.url_base());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn selects_profiles_from_urls() {
+        let profile = ServerProfile::from_url("https://fix.example/api/").unwrap();
+        assert_eq!(profile.url_base(), "https://fix.example:443/api");
+        let local = ServerProfile::from_url("127.0.0.1:9090").unwrap();
+        assert_eq!(local.url_base(), "http://127.0.0.1:9090");
+        assert!(ServerProfile::from_url("ftp://fix.example").is_err());
+
+        let mut profiles = ServerProfiles {
+            profiles: vec![ServerProfile::from_url("127.0.0.1:9090").unwrap()],
+            active: 0,
+        };
+        profiles.select(profile);
+        assert_eq!(profiles.active, 1);
+        profiles.select(local);
+        assert_eq!(profiles.active, 0);
+        assert_eq!(profiles.profiles.len(), 2);
+    }
+}
@@ -0,0 +1,114 @@
+use anyhow::Result;
+use egui::{Color32, RichText, Ui};
+
+use crate::{
+    bookmarks::is_bookmarks_file,
+    files,
+    graphs::{export, RelationStorage},
+    handle::Handle,
+    http::is_session_file,
+};
+
+/// A graph opened from an exported file instead of fetched from a server.
+pub(crate) struct Snapshot {
+    pub name: String,
+    /// Whether fetches go to the active server profile. Otherwise fetching is
+    /// disabled.
+    pub allow_live: bool,
+    /// The fetched graph and target from before the snapshot was opened.
+    pub live_graph: RelationStorage,
+    pub live_target: Handle,
+}
+
+/// Controls for opening and closing snapshots.
+#[derive(Default)]
+pub(crate) struct SnapshotPanel {
+    #[cfg_attr(target_arch = "wasm32", allow(dead_code))]
+    path: String,
+    status: String,
+}
+
+impl Snapshot {
+    /// Replaces `graph` with the snapshot in `json`, returning the root it was
+    /// exported from. The live graph is kept when switching between snapshots.
+    pub(crate) fn open(
+        current: &mut Option<Snapshot>,
+        name: String,
+        json: &str,
+        graph: &mut RelationStorage,
+        target: &Handle,
+    ) -> Result<Option<Handle>> {
+        let (snapshot_graph, root) = export::import(json)?;
+        let shown = std::mem::replace(graph, snapshot_graph);
+        let (live_graph, live_target) = match current.take() {
+            Some(previous) => (previous.live_graph, previous.live_target),
+            None => (shown, target.clone()),
+        };
+        *current = Some(Snapshot {
+            name,
+            allow_live: false,
+            live_graph,
+            live_target,
+        });
+        Ok(root)
+    }
+
+    /// Whether requests should fail instead of going to a server.
+    pub(crate) fn is_offline(&self) -> bool {
+        !self.allow_live
+    }
+
+    /// Shows that a snapshot is open, returning whether to close it.
+    pub(crate) fn banner(&mut self, ui: &mut Ui) -> bool {
+        ui.horizontal(|ui| {
+            ui.label(
+                RichText::new(format!("Snapshot: {}", self.name))
+                    .color(Color32::BLACK)
+                    .background_color(Color32::GOLD),
+            );
+            ui.checkbox(&mut self.allow_live, "fetch from server")
+                .on_hover_text("Send fetches to the active server profile");
+            ui.button("close snapshot").clicked()
+        })
+        .inner
+    }
+}
+
+impl SnapshotPanel {
+    /// Returns the name and contents of a file to open as a snapshot.
+    pub(crate) fn ui(&mut self, ui: &mut Ui) -> Option<(String, String)> {
+        ui.heading("Snapshot");
+        let mut opened = None;
+        #[cfg(not(target_arch = "wasm32"))]
+        ui.horizontal(|ui| {
+            ui.label("File:");
+            ui.text_edit_singleline(&mut self.path);
+            if ui.button("open").clicked() {
+                match files::read(&self.path) {
+                    Ok(contents) => opened = Some((self.path.clone(), contents)),
+                    Err(e) => self.status = format!("{e:#}"),
+                }
+            }
+        });
+        ui.small("Drop an exported JSON graph onto the window to open it.");
+
+        let dropped = ui.ctx().input(|i| i.raw.dropped_files.clone());
+        for file in dropped
+            .into_iter()
+            .filter(|f| !is_session_file(f) && !is_bookmarks_file(f))
+        {
+            match files::read_dropped(&file) {
+                Ok(contents) => opened = Some((file.name.clone(), contents)),
+                Err(e) => self.status = format!("{e:#}"),
+            }
+        }
+        if !self.status.is_empty() {
+            ui.small(&self.status);
+        }
+        opened
+    }
+
+    pub(crate) fn set_status(&mut self, status: String) {
+        self.status = status;
+    }
+}
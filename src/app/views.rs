@@ -1,11 +1,21 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
-use egui::{emath::TSTransform, TextEdit, Ui};
+use anyhow::Result;
+use egui::{
+    emath::TSTransform, text::CCursor, text_selection::CCursorRange, DragValue, Id, Key, Modifiers,
+    Pos2, RichText, TextEdit, Ui,
+};
 
 use crate::{
-    graphs::{add_main_node, add_node, get_connection, Ports, TransformClip},
-    handle::Handle,
-    http::{HttpContext, LogEntry, Request},
+    bookmarks::Bookmarks,
+    console::{self, script::LogHost, History},
+    graphs::RelationStorage,
+    graphs::{
+        add_main_node, add_node, get_connection, layout::layered_layout, NodeOptions, PortType,
+        Ports, TransformClip,
+    },
+    handle::{Handle, HEX_LENGTH},
+    http::{HttpContext, LogEntry, LogLine},
 };
 
 use super::{State, Storage};
@@ -17,7 +27,7 @@ pub enum View {
 }
 
 impl View {
-    pub fn draw(
+    pub(super) fn draw(
         &self,
         ui: &mut Ui,
         state: &mut State,
@@ -39,7 +49,21 @@ impl View {
 }
 
 pub fn graph_view(ui: &mut Ui, state: &mut State, storage: &mut Storage, http_ctx: &HttpContext) {
-    ui.heading("Objects");
+    let mut relayout = std::mem::take(&mut state.relayout);
+    ui.horizontal(|ui| {
+        ui.heading("Objects");
+        relayout |= ui
+            .button("re-layout")
+            .on_hover_text("Move every node that was not moved by hand to its automatic position")
+            .clicked();
+        if ui
+            .add_enabled(!state.pinned.is_empty(), egui::Button::new("unpin all"))
+            .on_hover_text("Let nodes that were moved by hand be laid out again")
+            .clicked()
+        {
+            state.pinned.clear();
+        }
+    });
     ui.separator();
 
     let (id, rect) = ui.allocate_space(ui.available_size());
@@ -79,102 +103,202 @@ pub fn graph_view(ui: &mut Ui, state: &mut State, storage: &mut Storage, http_ct
 
     let mut handle_to_ports: HashMap<Handle, Ports> = HashMap::new();
 
-    let main_handle = match Handle::from_hex(&state.target_input) {
-        Ok(h) => {
-            storage.target = h.clone();
-            h
-        }
-        Err(e) => {
-            state.error.write(format!("{:#}", e));
-            storage.target.clone()
+    let submitted = std::mem::take(&mut state.target_submitted);
+    match resolve_target_input(
+        &mut state.target_input,
+        submitted,
+        &storage.bookmarks,
+        &state.connections,
+    ) {
+        Some(Ok(h)) => {
+            storage.target = h;
+            state.target_error.clear();
         }
+        Some(Err(e)) => state.target_error = format!("{e:#}"),
+        None => {}
+    }
+    let main_handle = storage.target.clone();
+
+    let layout = layered_layout(&state.connections, &main_handle);
+    let pinned = &mut state.pinned;
+    let selected = &state.selected;
+    let notifications = &state.notifications;
+    let bookmarks = &storage.bookmarks;
+    let options = |handle: &Handle, pinned: &HashSet<Handle>| NodeOptions {
+        pos: layout.get(handle).copied().unwrap_or(Pos2::new(20.0, 20.0)),
+        reset: relayout && !pinned.contains(handle),
+        selected: selected.contains(handle),
+        notifications: notifications.count_for(handle),
+        alias: bookmarks.alias(handle).map(str::to_owned),
     };
 
-    handle_to_ports.insert(
+    let main_ports = add_main_node(
+        http_ctx.clone(),
         main_handle.clone(),
-        add_main_node(
-            http_ctx.clone(),
-            main_handle.clone(),
-            &state.connections,
-            &mut state.target_input,
-            state.error.read(),
-            clip.clone(),
-        ),
+        &state.connections,
+        &mut state.target_input,
+        &state.target_error,
+        options(&main_handle, pinned),
+        clip.clone(),
     );
+    if main_ports.dragged {
+        pinned.insert(main_handle.clone());
+    }
+    state.target_submitted = main_ports.submitted;
+    let mut expand = main_ports.expand.then(|| main_handle.clone());
+    let mut show_notifications = main_ports.show_notifications.then(|| main_handle.clone());
+    let mut inspect = None;
+    handle_to_ports.insert(main_handle.clone(), main_ports);
 
-    let painter = ui.painter();
-    let painter = painter.with_clip_rect(rect);
+    let mut connections = vec![];
     state.connections.visit_bfs(main_handle.clone(), {
-        let connections = &state.connections;
+        let graph = &state.connections;
+        let connections = &mut connections;
+        let clip = &clip;
+        let expand = &mut expand;
+        let show_notifications = &mut show_notifications;
+        let inspect = &mut inspect;
         move |connection| {
             if let Some((port_type, rhs)) = connection.rhs.get_port_type() {
+                let mut add_node = |handle: &Handle| {
+                    let ports = add_node(
+                        http_ctx.clone(),
+                        handle.clone(),
+                        graph,
+                        options(handle, pinned),
+                        clip.clone(),
+                    );
+                    if ports.dragged {
+                        pinned.insert(handle.clone());
+                    }
+                    if ports.expand {
+                        *expand = Some(handle.clone());
+                    }
+                    if ports.show_notifications {
+                        *show_notifications = Some(handle.clone());
+                    }
+                    if ports.inspect {
+                        *inspect = Some(handle.clone());
+                    }
+                    ports
+                };
                 let out_port = *handle_to_ports
                     .entry(connection.lhs.clone())
-                    .or_insert_with({
-                        || {
-                            add_node(
-                                http_ctx.clone(),
-                                connection.lhs.clone(),
-                                connections,
-                                clip.clone(),
-                            )
-                        }
-                    })
+                    .or_insert_with(|| add_node(&connection.lhs))
                     .outputs
                     .get(&port_type)
                     .expect("Connection without port");
                 let in_port = handle_to_ports
                     .entry(rhs.clone())
-                    .or_insert_with({
-                        let clip = clip.clone();
-                        || add_node(http_ctx.clone(), rhs.clone(), connections, clip)
-                    })
+                    .or_insert_with(|| add_node(&rhs))
                     .input;
-                let clip = clip.clone();
-                // TODO: clip bezier
-                painter.add(get_connection(
+                let label = match port_type {
+                    PortType::TagTarget => graph.get_tag_label(&connection.lhs),
+                    _ => None,
+                };
+                connections.push(get_connection(
+                    connection.clone(),
                     out_port,
                     in_port,
                     port_type,
-                    connection.lhs == rhs,
-                    clip,
+                    label,
+                    clip.clone(),
                 ));
             }
         }
     });
+
+    if let Some(handle) = show_notifications {
+        state.notifications.show_for(handle);
+    }
+    if let Some(handle) = inspect {
+        let via = state
+            .connections
+            .path(&main_handle, &handle)
+            .unwrap_or_default();
+        state.target_input = handle.to_hex();
+        storage.navigation.visit(handle.clone(), via);
+        storage.target = handle;
+        state.relayout = true;
+    }
+    if let Some(handle) = expand {
+        state.crawler.start(handle);
+        // Requests are sent at the start of the next frame.
+        ui.ctx().request_repaint();
+    }
+
+    let painter = ui.painter().with_clip_rect(rect.intersect(ui.clip_rect()));
+    // Nodes are above the canvas, so it is only hovered when no node is.
+    let hovered = response
+        .hover_pos()
+        .and_then(|pointer| connections.iter().position(|c| c.hit(pointer)));
+    for (i, connection) in connections.iter().enumerate() {
+        connection.paint(&painter, hovered == Some(i), clip.clone());
+    }
+
+    if let Some(connection) = hovered.map(|i| &connections[i]) {
+        egui::show_tooltip_at_pointer(ui.ctx(), id.with("connection tooltip"), |ui| {
+            ui.monospace(connection.tooltip());
+        });
+        if response.clicked() {
+            let relation = &connection.relation;
+            state.selected = HashSet::from([relation.lhs.clone()]);
+            if let Some((_, rhs)) = relation.rhs.get_port_type() {
+                state.selected.insert(rhs);
+            }
+        }
+    } else if response.clicked() {
+        state.selected.clear();
+    }
+}
+
+/// Resolves the target field. Whole handles take effect as they are typed,
+/// while short handles and bookmarks wait until the field is `submitted`, so
+/// that a handle being typed isn't completed under the cursor. Returns `None`
+/// while waiting.
+fn resolve_target_input(
+    input: &mut String,
+    submitted: bool,
+    bookmarks: &Bookmarks,
+    graph: &RelationStorage,
+) -> Option<Result<Handle>> {
+    let text = input.trim();
+    let bookmark = text.starts_with('@');
+    if !submitted && (bookmark || text.len() < HEX_LENGTH) {
+        return None;
+    }
+    let result = bookmarks.parse_handle(text, graph);
+    // A shortened handle could become ambiguous as more handles are
+    // fetched, so it is replaced by the whole handle once resolved.
+    if let (Ok(handle), false) = (&result, bookmark) {
+        *input = handle.to_hex();
+    }
+    Some(result)
 }
 
-pub fn text_view(ui: &mut Ui, state: &mut State, storage: &mut Storage, http_ctx: &HttpContext) {
+pub fn text_view(_ui: &mut Ui, state: &mut State, storage: &mut Storage, http_ctx: &HttpContext) {
     egui::TopBottomPanel::bottom("text_console").show(&http_ctx.egui_ctx, |ui| {
+        add_console(ui, state, storage, http_ctx);
+    });
+    egui::CentralPanel::default().show(&http_ctx.egui_ctx, |ui| {
         ui.horizontal(|ui| {
-            ui.label("> ");
-            let response = TextEdit::singleline(&mut state.log.command_input)
-                .desired_width(f32::INFINITY)
-                .show(ui)
-                .response;
-            if response.lost_focus() && http_ctx.egui_ctx.input(|i| i.key_pressed(egui::Key::Enter))
-            {
-                Request::parse_send(state.log.command_input.clone(), http_ctx.clone());
-                response.request_focus();
+            ui.heading("Bonjour  ");
+            ui.label("History length:");
+            let max_len = DragValue::new(&mut storage.history.max_len).clamp_range(0..=10_000);
+            if ui.add(max_len).changed() {
+                storage.history.truncate();
             }
         });
-    });
-    egui::CentralPanel::default().show(&http_ctx.egui_ctx, |ui| {
-        ui.heading("Bonjour  ");
-        ui.separator();
-        ui.label(format!("Error: {}", state.error.read()));
         ui.separator();
         egui::ScrollArea::both()
             .stick_to_bottom(true)
             .show(ui, |ui| {
                 ui.style_mut().wrap = Some(false);
-                for (i, entry) in &state.log.log {
-                    match entry {
-                        LogEntry::Request(r) => {
-                            ui.monospace(format!("[{i}]: > {}", r));
-                        }
-                        LogEntry::Response(c) => {
-                            ui.monospace(format!("[{i}]: {} {}", c.lhs.to_hex(), c.rhs));
+                for line in &state.log.log {
+                    match line {
+                        LogLine::Request(entry) => add_log_entry(ui, entry),
+                        LogLine::Text(text) => {
+                            ui.monospace(text);
                         }
                     }
                 }
@@ -183,3 +307,202 @@ pub fn text_view(ui: &mut Ui, state: &mut State, storage: &mut Storage, http_ctx
             });
     });
 }
+
+/// The command line under the text view. Up and down move through the
+/// history, Ctrl+R searches it and Tab completes the word being typed.
+fn add_console(ui: &mut Ui, state: &mut State, storage: &mut Storage, http_ctx: &HttpContext) {
+    let input_id = ui.make_persistent_id("console input");
+    let Storage {
+        history, bookmarks, ..
+    } = storage;
+    if history.search().is_some() {
+        add_history_search(ui, history, &mut state.log.command_input, input_id);
+        return;
+    }
+    if !state.log.completions.is_empty() {
+        ui.horizontal_wrapped(|ui| {
+            for completion in &state.log.completions {
+                ui.monospace(completion);
+            }
+        });
+    }
+
+    // Keys are taken before the text edit would handle them.
+    let focused = ui.memory(|m| m.has_focus(input_id));
+    let pressed = |modifiers, key| focused && ui.input_mut(|i| i.consume_key(modifiers, key));
+    let mut replaced = None;
+    if pressed(Modifiers::NONE, Key::ArrowUp) {
+        replaced = history.older(&state.log.command_input);
+    } else if pressed(Modifiers::NONE, Key::ArrowDown) {
+        replaced = history.newer();
+    } else if pressed(Modifiers::CTRL, Key::R) {
+        history.start_search();
+        ui.memory_mut(|m| m.request_focus(input_id.with("search")));
+    } else if pressed(Modifiers::NONE, Key::Tab) {
+        let completion = console::complete(
+            &state.log.command_input,
+            state.connections.handles(),
+            bookmarks.names(),
+        );
+        state.log.completions = completion.candidates;
+        replaced = Some(completion.input);
+    }
+    if let Some(replaced) = &replaced {
+        state.log.command_input = replaced.clone();
+    }
+
+    ui.horizontal(|ui| {
+        if state.script.is_running() && ui.button("stop").clicked() {
+            state.script.cancel();
+        }
+        ui.label("> ");
+        let mut output = TextEdit::singleline(&mut state.log.command_input)
+            .id(input_id)
+            .desired_width(f32::INFINITY)
+            .hint_text("Type help to list the commands")
+            // Keeps the focus when Tab is pressed, which completes instead.
+            .lock_focus(true)
+            .show(ui);
+        let response = output.response;
+        if replaced.is_some() {
+            let end = CCursor::new(state.log.command_input.chars().count());
+            output
+                .state
+                .cursor
+                .set_char_range(Some(CCursorRange::one(end)));
+            output.state.store(ui.ctx(), response.id);
+        } else if response.changed() {
+            state.log.completions.clear();
+        }
+        if response.lost_focus() && ui.input(|i| i.key_pressed(Key::Enter)) {
+            let command = state.log.command_input.clone();
+            history.push(&command);
+            state.script.run(&command);
+            let mut host = LogHost {
+                ctx: http_ctx,
+                log: &mut state.log,
+            };
+            state.script.pump(&mut host, bookmarks, &state.connections);
+            state.log.completions.clear();
+            response.request_focus();
+        }
+    });
+}
+
+/// Searches the history for a command to put in the console. Enter accepts
+/// the command found and Escape cancels.
+fn add_history_search(ui: &mut Ui, history: &mut History, input: &mut String, input_id: Id) {
+    let search_id = input_id.with("search");
+    let older = ui.memory(|m| m.has_focus(search_id))
+        && ui.input_mut(|i| i.consume_key(Modifiers::CTRL, Key::R));
+    ui.horizontal(|ui| {
+        ui.label("reverse search:");
+        let Some(search) = history.search() else {
+            return;
+        };
+        let response = TextEdit::singleline(&mut search.query)
+            .id(search_id)
+            .desired_width(200.0)
+            .show(ui)
+            .response;
+        if older || response.changed() {
+            history.find(older);
+        }
+        match history.found() {
+            Some(found) => ui.monospace(found),
+            None => ui.weak("no match"),
+        };
+        if response.lost_focus() {
+            if ui.input(|i| i.key_pressed(Key::Enter)) {
+                if let Some(found) = history.found() {
+                    *input = found.to_owned();
+                }
+            }
+            history.stop_search();
+            ui.memory_mut(|m| m.request_focus(input_id));
+        }
+    });
+}
+
+/// Shows a request, with the relations it produced or its error under it.
+fn add_log_entry(ui: &mut Ui, entry: &LogEntry) {
+    let status = match (&entry.result, entry.status, entry.merged_into) {
+        (None, _, None) => "pending".to_owned(),
+        (None, _, Some(into)) => format!("waiting on #{into}"),
+        (Some(_), Some(status), _) => status.to_string(),
+        (Some(_), None, Some(into)) => format!("shared #{into}"),
+        (Some(Ok(_)), None, None) => "cached".to_owned(),
+        (Some(Err(_)), None, None) => "failed".to_owned(),
+    };
+    let duration = entry
+        .completed
+        .map(|completed| format!(", {:.0} ms", (completed - entry.sent) * 1000.0))
+        .unwrap_or_default();
+    let mut header = RichText::new(format!(
+        "[{}]: > {}  ({status}{duration})",
+        entry.id, entry.command
+    ))
+    .monospace();
+    if entry.request.is_none() {
+        header = header.color(ui.visuals().error_fg_color);
+    }
+    egui::CollapsingHeader::new(header)
+        .id_source(("log entry", entry.id))
+        .show(ui, |ui| match &entry.result {
+            None => {}
+            Some(Ok(relations)) if relations.is_empty() => {
+                ui.label("no relations");
+            }
+            Some(Ok(relations)) => {
+                for c in relations {
+                    ui.monospace(format!("{} {}", c.lhs.to_hex(), c.rhs));
+                }
+            }
+            Some(Err(e)) => {
+                ui.colored_label(ui.visuals().error_fg_color, e.kind.to_string());
+            }
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        graphs::{Relation, RelationRhs},
+        handle::test_handle,
+    };
+
+    #[test]
+    fn waits_to_resolve_short_targets() {
+        let mut graph = RelationStorage::default();
+        graph.insert(Relation::new(
+            test_handle(0xab),
+            RelationRhs::Eval(test_handle(1)),
+        ));
+        let mut bookmarks = Bookmarks::default();
+        bookmarks
+            .insert("input", test_handle(1), String::new())
+            .unwrap();
+
+        // A handle being typed that starts like a known one is left alone.
+        let mut input = "abab".to_owned();
+        assert!(resolve_target_input(&mut input, false, &bookmarks, &graph).is_none());
+        assert_eq!(input, "abab");
+        let mut input = "@input".to_owned();
+        assert!(resolve_target_input(&mut input, false, &bookmarks, &graph).is_none());
+
+        let mut input = "abab".to_owned();
+        let resolved = resolve_target_input(&mut input, true, &bookmarks, &graph);
+        assert_eq!(resolved.unwrap().unwrap(), test_handle(0xab));
+        assert_eq!(input, test_handle(0xab).to_hex());
+        let mut input = "@input".to_owned();
+        let resolved = resolve_target_input(&mut input, true, &bookmarks, &graph);
+        assert_eq!(resolved.unwrap().unwrap(), test_handle(1));
+        assert_eq!(input, "@input");
+
+        // Whole handles take effect at once.
+        let mut input = test_handle(2).to_hex();
+        let resolved = resolve_target_input(&mut input, false, &bookmarks, &graph);
+        assert_eq!(resolved.unwrap().unwrap(), test_handle(2));
+    }
+}
@@ -0,0 +1,10 @@
+#![warn(clippy::all, rust_2018_idioms)]
+
+// The client only runs natively. See `fix_viewer::query`.
+#[cfg(not(target_arch = "wasm32"))]
+fn main() -> std::process::ExitCode {
+    fix_viewer::query::main(std::env::args().skip(1))
+}
+
+#[cfg(target_arch = "wasm32")]
+fn main() {}
@@ -0,0 +1,352 @@
+//! Names for well-known handles, which can be written as `@name` wherever a
+//! handle is accepted.
+//!
+//! Bookmarks are shared as JSON files ending in `.bookmarks.json`:
+//!
+//! ```json
+//! {
+//!   "version": 1,
+//!   "bookmarks": [
+//!     { "name": "add-simple", "handle": "<64 hex characters>", "note": "adds two numbers" }
+//!   ]
+//! }
+//! ```
+
+use std::collections::BTreeMap;
+
+use anyhow::{ensure, Context, Result};
+use egui::{RichText, TextEdit, Ui};
+
+use crate::{files, graphs::RelationStorage, handle::Handle};
+
+const VERSION: u32 = 1;
+
+/// The bookmarks, by name.
+#[derive(Default, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub(crate) struct Bookmarks {
+    entries: BTreeMap<String, Bookmark>,
+}
+
+#[derive(Clone, Debug, PartialEq, serde::Deserialize, serde::Serialize)]
+pub(crate) struct Bookmark {
+    pub handle: Handle,
+    pub note: String,
+}
+
+#[derive(serde::Deserialize, serde::Serialize)]
+struct JsonBookmarks {
+    version: u32,
+    bookmarks: Vec<JsonBookmark>,
+}
+
+#[derive(serde::Deserialize, serde::Serialize)]
+struct JsonBookmark {
+    name: String,
+    handle: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    note: String,
+}
+
+/// Controls for editing, importing and exporting bookmarks.
+pub(crate) struct BookmarksPanel {
+    /// The name to bookmark the target as.
+    name: String,
+    file_name: String,
+    status: String,
+}
+
+impl Bookmarks {
+    pub(crate) fn get(&self, name: &str) -> Option<&Bookmark> {
+        self.entries.get(name)
+    }
+
+    pub(crate) fn names(&self) -> impl Iterator<Item = &str> {
+        self.entries.keys().map(String::as_str)
+    }
+
+    /// The first name the handle is bookmarked as.
+    pub(crate) fn alias(&self, handle: &Handle) -> Option<&str> {
+        self.entries
+            .iter()
+            .find(|(_, bookmark)| bookmark.handle == *handle)
+            .map(|(name, _)| name.as_str())
+    }
+
+    /// Adds or replaces a bookmark. Names are what follows `@` in commands,
+    /// so they are made of letters, digits, `-`, `_` and `.`.
+    pub(crate) fn insert(&mut self, name: &str, handle: Handle, note: String) -> Result<()> {
+        let name = parse_name(name)?;
+        self.entries
+            .insert(name.to_owned(), Bookmark { handle, note });
+        Ok(())
+    }
+
+    /// Parses a handle, which is either `@name` or hex which can be
+    /// shortened as [`RelationStorage::resolve`] allows.
+    pub(crate) fn parse_handle(&self, text: &str, graph: &RelationStorage) -> Result<Handle> {
+        let text = text.trim();
+        match text.strip_prefix('@') {
+            Some(name) => self.lookup(name),
+            None => graph.resolve(text),
+        }
+    }
+
+    /// Replaces every `@name` word of a command with the handle's hex.
+    pub(crate) fn expand(&self, command: &str) -> Result<String> {
+        let words: Result<Vec<String>> = command
+            .split_whitespace()
+            .map(|word| match word.strip_prefix('@') {
+                Some(name) => self.lookup(name).map(|handle| handle.to_hex()),
+                None => Ok(word.to_owned()),
+            })
+            .collect();
+        Ok(words?.join(" "))
+    }
+
+    fn lookup(&self, name: &str) -> Result<Handle> {
+        self.get(name)
+            .map(|bookmark| bookmark.handle.clone())
+            .with_context(|| format!("no bookmark named @{name}"))
+    }
+
+    pub(crate) fn to_json(&self) -> Result<String> {
+        let json = JsonBookmarks {
+            version: VERSION,
+            bookmarks: self
+                .entries
+                .iter()
+                .map(|(name, bookmark)| JsonBookmark {
+                    name: name.clone(),
+                    handle: bookmark.handle.to_hex(),
+                    note: bookmark.note.clone(),
+                })
+                .collect(),
+        };
+        serde_json::to_string_pretty(&json).context("serializing bookmarks")
+    }
+
+    /// Adds the bookmarks in a shared file, replacing those with the same
+    /// names, and returns how many there were.
+    pub(crate) fn import(&mut self, json: &str) -> Result<usize> {
+        let json: JsonBookmarks = serde_json::from_str(json).context("parsing bookmarks")?;
+        ensure!(
+            json.version == VERSION,
+            "unsupported bookmarks version {}",
+            json.version
+        );
+        // Nothing is imported if any bookmark is invalid.
+        let mut imported = vec![];
+        for bookmark in json.bookmarks {
+            let name = parse_name(&bookmark.name)?.to_owned();
+            let handle = Handle::from_hex(&bookmark.handle)
+                .with_context(|| format!("invalid handle for @{name}"))?;
+            imported.push((
+                name,
+                Bookmark {
+                    handle,
+                    note: bookmark.note,
+                },
+            ));
+        }
+        let count = imported.len();
+        self.entries.extend(imported);
+        Ok(count)
+    }
+}
+
+/// Checks a bookmark name, with or without the leading `@`, and returns it
+/// without.
+fn parse_name(name: &str) -> Result<&str> {
+    let name = name.trim().trim_start_matches('@');
+    ensure!(!name.is_empty(), "bookmark names cannot be empty");
+    ensure!(
+        name.chars()
+            .all(|c| c.is_ascii_alphanumeric() || ['-', '_', '.'].contains(&c)),
+        "bookmark names can only contain letters, digits, -, _ and ., not @{name}"
+    );
+    Ok(name)
+}
+
+impl Default for BookmarksPanel {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            file_name: "team.bookmarks.json".to_owned(),
+            status: String::new(),
+        }
+    }
+}
+
+impl BookmarksPanel {
+    /// Returns a handle to go to if a bookmark was clicked.
+    pub(crate) fn ui(
+        &mut self,
+        ui: &mut Ui,
+        bookmarks: &mut Bookmarks,
+        target: &Handle,
+    ) -> Option<Handle> {
+        ui.heading("Bookmarks");
+        let mut go_to = None;
+        let mut removed = None;
+        for (name, bookmark) in &mut bookmarks.entries {
+            ui.horizontal(|ui| {
+                if ui.small_button("×").on_hover_text("Remove").clicked() {
+                    removed = Some(name.clone());
+                }
+                if ui
+                    .link(RichText::new(format!("@{name}")).monospace())
+                    .on_hover_text(bookmark.handle.to_hex())
+                    .clicked()
+                {
+                    go_to = Some(bookmark.handle.clone());
+                }
+                ui.add(
+                    TextEdit::singleline(&mut bookmark.note)
+                        .hint_text("note")
+                        .desired_width(f32::INFINITY),
+                );
+            });
+        }
+        if let Some(name) = removed {
+            bookmarks.entries.remove(&name);
+        }
+        ui.horizontal(|ui| {
+            ui.add(
+                TextEdit::singleline(&mut self.name)
+                    .hint_text("name")
+                    .desired_width(120.0),
+            );
+            if ui.button("bookmark target").clicked() {
+                match bookmarks.insert(&self.name, target.clone(), String::new()) {
+                    Ok(()) => {
+                        self.name.clear();
+                        self.status.clear();
+                    }
+                    Err(e) => self.status = format!("{e:#}"),
+                }
+            }
+        });
+
+        ui.horizontal(|ui| {
+            ui.label("File:");
+            ui.text_edit_singleline(&mut self.file_name);
+        });
+        ui.horizontal(|ui| {
+            #[cfg(not(target_arch = "wasm32"))]
+            if ui.button("import").clicked() {
+                let result = files::read(&self.file_name).and_then(|s| bookmarks.import(&s));
+                self.set_import_status(result);
+            }
+            if ui.button("export").clicked() {
+                let result = bookmarks
+                    .to_json()
+                    .and_then(|json| files::save(&self.file_name, &json));
+                self.status = match result {
+                    Ok(()) => format!("exported {} bookmarks", bookmarks.entries.len()),
+                    Err(e) => format!("{e:#}"),
+                };
+            }
+        });
+        ui.small("Drop a .bookmarks.json file onto the window to import it.");
+
+        let dropped = ui.ctx().input(|i| i.raw.dropped_files.clone());
+        for file in dropped.iter().filter(|f| is_bookmarks_file(f)) {
+            let result = files::read_dropped(file).and_then(|s| bookmarks.import(&s));
+            self.set_import_status(result);
+        }
+        if !self.status.is_empty() {
+            ui.small(&self.status);
+        }
+        go_to
+    }
+
+    fn set_import_status(&mut self, result: Result<usize>) {
+        self.status = match result {
+            Ok(count) => format!("imported {count} bookmarks"),
+            Err(e) => format!("{e:#}"),
+        };
+    }
+}
+
+/// Whether a dropped file holds bookmarks rather than a snapshot.
+pub(crate) fn is_bookmarks_file(file: &egui::DroppedFile) -> bool {
+    file.name.ends_with(".bookmarks.json")
+}
+
+/// Reads a bookmarks file, for the command-line client.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn read(path: &str) -> Result<Bookmarks> {
+    let mut bookmarks = Bookmarks::default();
+    bookmarks.import(&files::read(path)?)?;
+    Ok(bookmarks)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::handle::test_handle;
+
+    #[test]
+    fn expands_aliases() {
+        let mut bookmarks = Bookmarks::default();
+        bookmarks
+            .insert("@add-simple", test_handle(1), String::new())
+            .unwrap();
+        assert!(bookmarks
+            .insert("two words", test_handle(2), String::new())
+            .is_err());
+        assert!(bookmarks
+            .insert("@", test_handle(2), String::new())
+            .is_err());
+        assert_eq!(
+            bookmarks.expand("relations  @add-simple eval").unwrap(),
+            format!("relations {} eval", test_handle(1).to_hex())
+        );
+        assert!(bookmarks.expand("contents @missing").is_err());
+        let graph = RelationStorage::default();
+        assert_eq!(
+            bookmarks.parse_handle(" @add-simple ", &graph).unwrap(),
+            test_handle(1)
+        );
+        assert_eq!(
+            bookmarks
+                .parse_handle(&test_handle(3).to_hex(), &graph)
+                .unwrap(),
+            test_handle(3)
+        );
+        assert_eq!(bookmarks.alias(&test_handle(1)), Some("add-simple"));
+    }
+
+    #[test]
+    fn round_trips_files() {
+        let mut bookmarks = Bookmarks::default();
+        bookmarks
+            .insert("input", test_handle(1), "nine".to_owned())
+            .unwrap();
+        let json = bookmarks.to_json().unwrap();
+        let mut imported = Bookmarks::default();
+        imported
+            .insert("input", test_handle(2), String::new())
+            .unwrap();
+        assert_eq!(imported.import(&json).unwrap(), 1);
+        assert_eq!(imported.get("input"), bookmarks.get("input"));
+
+        let invalid = json.replace(&test_handle(1).to_hex(), "ab");
+        assert!(imported.import(&invalid).is_err());
+        assert!(imported
+            .import("{\"version\": 2, \"bookmarks\": []}")
+            .is_err());
+
+        // A bad name after a good bookmark leaves both out.
+        let hex = test_handle(3).to_hex();
+        let invalid = format!(
+            r#"{{"version": 1, "bookmarks": [
+                {{"name": "fresh", "handle": "{hex}"}},
+                {{"name": "two words", "handle": "{hex}"}}
+            ]}}"#
+        );
+        assert!(imported.import(&invalid).is_err());
+        assert!(imported.get("fresh").is_none());
+    }
+}
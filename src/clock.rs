@@ -0,0 +1,61 @@
+//! Wall-clock time, which `std::time` can't tell on the web.
+
+/// Seconds since the Unix epoch.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn now() -> f64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0.0, |since| since.as_secs_f64())
+}
+
+/// Seconds since the Unix epoch.
+#[cfg(target_arch = "wasm32")]
+pub(crate) fn now() -> f64 {
+    js_sys::Date::now() / 1000.0
+}
+
+/// The time zone [`time_of_day`] formats times in.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) const TIME_ZONE: &str = "UTC";
+
+/// The time zone [`time_of_day`] formats times in.
+#[cfg(target_arch = "wasm32")]
+pub(crate) const TIME_ZONE: &str = "local time";
+
+/// Formats a time from [`now`] as the time of day, like `14:03:09 UTC`.
+/// Natively that is in UTC, since `std` doesn't know the local time zone.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn time_of_day(time: f64) -> String {
+    let seconds = time as u64 % (24 * 60 * 60);
+    format!(
+        "{:02}:{:02}:{:02} {TIME_ZONE}",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    )
+}
+
+/// Formats a time from [`now`] as the local time of day, like `14:03:09`.
+#[cfg(target_arch = "wasm32")]
+pub(crate) fn time_of_day(time: f64) -> String {
+    let date = js_sys::Date::new(&wasm_bindgen::JsValue::from_f64(time * 1000.0));
+    format!(
+        "{:02}:{:02}:{:02}",
+        date.get_hours(),
+        date.get_minutes(),
+        date.get_seconds()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_times_of_day() {
+        // 2024-05-01 13:04:05 UTC.
+        assert_eq!(time_of_day(1_714_568_645.7), "13:04:05 UTC");
+        assert_eq!(time_of_day(0.0), "00:00:00 UTC");
+        assert!(now() > 1_714_568_645.0);
+    }
+}
@@ -0,0 +1,347 @@
+mod history;
+pub(crate) mod script;
+
+use std::fmt::Write;
+
+use crate::{graphs::RelationStorage, handle::Handle};
+
+pub(crate) use self::{history::History, script::Runner};
+
+/// The most completions listed under the console.
+const MAX_CANDIDATES: usize = 12;
+
+/// A command understood by the text console.
+pub(crate) struct Command {
+    pub name: &'static str,
+    args: &'static [Arg],
+    summary: &'static str,
+}
+
+#[derive(Clone, Copy)]
+enum Arg {
+    Handle,
+    Op,
+    /// A number which can be left out.
+    Number(&'static str),
+    Command,
+    File,
+}
+
+pub(crate) const COMMANDS: &[Command] = &[
+    Command {
+        name: "explanations",
+        args: &[Arg::Handle],
+        summary: "Get the eval and apply relations whose result is the handle.",
+    },
+    Command {
+        name: "contents",
+        args: &[Arg::Handle],
+        summary: "Get the entries of a tree or thunk.",
+    },
+    Command {
+        name: "description",
+        args: &[Arg::Handle],
+        summary: "Get the server's description of the handle.",
+    },
+    Command {
+        name: "relations",
+        args: &[Arg::Handle, Arg::Op],
+        summary: "Get the result of evaluating or applying the handle.",
+    },
+    Command {
+        name: "eval",
+        args: &[Arg::Handle],
+        summary: "Short for relations <handle> eval.",
+    },
+    Command {
+        name: "apply",
+        args: &[Arg::Handle],
+        summary: "Short for relations <handle> apply.",
+    },
+    Command {
+        name: "pins",
+        args: &[Arg::Handle],
+        summary: "Get the objects that pin the handle, and the contents of those that are tags.",
+    },
+    Command {
+        name: "tag",
+        args: &[Arg::Handle],
+        summary: "Get the target, author and label of a tag.",
+    },
+    Command {
+        name: "blob",
+        args: &[Arg::Handle, Arg::Number("offset"), Arg::Number("length")],
+        summary: "Get the bytes of a blob. The offset defaults to 0 and the length to 256.",
+    },
+    Command {
+        name: "source",
+        args: &[Arg::File],
+        summary: "Run the commands in a script file.",
+    },
+    Command {
+        name: "help",
+        args: &[Arg::Command],
+        summary: "List the commands, or describe one of them.",
+    },
+];
+
+/// The result of completing the word before the cursor.
+#[derive(Debug, PartialEq)]
+pub(crate) struct Completion {
+    /// The input with the word completed as far as it is unambiguous.
+    pub input: String,
+    /// The possible completions, if there is more than one.
+    pub candidates: Vec<String>,
+}
+
+impl Command {
+    fn find(name: &str) -> Option<&'static Command> {
+        COMMANDS.iter().find(|c| c.name == name)
+    }
+
+    fn usage(&self) -> String {
+        let mut usage = self.name.to_owned();
+        for arg in self.args {
+            usage.push(' ');
+            usage += &arg.to_string();
+        }
+        usage
+    }
+}
+
+impl std::fmt::Display for Arg {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Arg::Handle => f.write_str("<handle>"),
+            Arg::Op => f.write_str("<eval|apply>"),
+            Arg::Number(name) => write!(f, "[{name}]"),
+            Arg::Command => f.write_str("[command]"),
+            Arg::File => f.write_str("<file>"),
+        }
+    }
+}
+
+/// Answers `help` and `help <command>`, or returns `None` for other input.
+pub(crate) fn help(input: &str) -> Option<String> {
+    let mut words = input.split_whitespace();
+    if words.next() != Some("help") {
+        return None;
+    }
+    let mut text = String::new();
+    match words.next() {
+        None => {
+            let width = COMMANDS.iter().map(|c| c.usage().len()).max().unwrap_or(0);
+            text += "Commands:";
+            for command in COMMANDS {
+                let _ = write!(text, "\n  {:width$}  {}", command.usage(), command.summary);
+            }
+            text += "\nA handle is 64 hex characters, or its first 4 or more if no other \
+                     known handle starts the same way, or @name for the handle \
+                     bookmarked as name. Press Tab to complete commands, operations, \
+                     handles in the graph and bookmarks.\n\
+                     Separate commands with ; to run them in turn, or with | to run the \
+                     next command on every handle the previous one returned, as in \
+                     contents <handle> | eval. $_ stands for every handle the last \
+                     command returned and $_0, $_1... for one of them.";
+        }
+        Some(name) => match Command::find(name) {
+            Some(command) => {
+                let _ = write!(text, "{}\n  {}", command.usage(), command.summary);
+            }
+            None => {
+                let _ = write!(
+                    text,
+                    "Unknown command {name}. Type help to list the commands."
+                );
+            }
+        },
+    }
+    Some(text)
+}
+
+/// Replaces the shortened handles given as arguments to `command` with the
+/// handles in `graph` they start, leaving `$_` for the runner. A piped
+/// handle takes the place of the first argument.
+pub(crate) fn resolve_handles(
+    command: &str,
+    piped: bool,
+    graph: &RelationStorage,
+) -> anyhow::Result<String> {
+    let mut words = command.split_whitespace();
+    let Some(name) = words.next() else {
+        return Ok(String::new());
+    };
+    let args = Command::find(name).map_or(&[][..], |c| c.args);
+    let mut resolved = vec![name.to_owned()];
+    for (i, word) in words.enumerate() {
+        let is_handle = matches!(args.get(i + usize::from(piped)), Some(Arg::Handle));
+        if is_handle && !word.starts_with('$') {
+            resolved.push(graph.resolve(word)?.to_hex());
+        } else {
+            resolved.push(word.to_owned());
+        }
+    }
+    Ok(resolved.join(" "))
+}
+
+/// Completes the last word of `input` from the commands, operations,
+/// `handles` and bookmark `aliases`, depending on where the word is in the
+/// last command.
+pub(crate) fn complete<'a>(
+    input: &str,
+    handles: impl Iterator<Item = &'a Handle>,
+    aliases: impl Iterator<Item = &'a str>,
+) -> Completion {
+    let start = input.rfind(char::is_whitespace).map_or(0, |i| i + 1);
+    let (before, word) = input.split_at(start);
+    let command_start = before.rfind([';', '|']).map_or(0, |i| i + 1);
+    let command = &before[command_start..];
+    let mut position = command.split_whitespace().count();
+    // A piped handle takes the place of the first argument.
+    if position > 0 && before[..command_start].ends_with('|') {
+        position += 1;
+    }
+
+    let arg = match position {
+        0 => Some(Arg::Command),
+        _ => command
+            .split_whitespace()
+            .next()
+            .and_then(Command::find)
+            .and_then(|c| c.args.get(position - 1).copied()),
+    };
+    let word_lowercase = word.to_lowercase();
+    let mut candidates: Vec<String> = match arg {
+        Some(Arg::Command) => COMMANDS.iter().map(|c| c.name.to_owned()).collect(),
+        Some(Arg::Op) => vec!["apply".to_owned(), "eval".to_owned()],
+        Some(Arg::Handle) if word.starts_with('@') => {
+            aliases.map(|name| format!("@{name}")).collect()
+        }
+        Some(Arg::Handle) => handles.map(Handle::to_hex).collect(),
+        Some(Arg::Number(_) | Arg::File) | None => vec![],
+    };
+    // Hex is completed whatever its case, while aliases keep theirs.
+    candidates.retain(|c| c.starts_with(&word_lowercase) || c.starts_with(word));
+    candidates.sort();
+    candidates.dedup();
+
+    match candidates.as_slice() {
+        [] => Completion {
+            input: input.to_owned(),
+            candidates,
+        },
+        [only] => Completion {
+            input: format!("{before}{only} "),
+            candidates: vec![],
+        },
+        [first, .., last] => {
+            // The candidates are sorted, so the first and last differ the most.
+            // They are all ASCII, so characters can be counted as bytes.
+            let common = first
+                .chars()
+                .zip(last.chars())
+                .take_while(|(a, b)| a == b)
+                .count();
+            let input = format!("{before}{}", &first[..common]);
+            candidates.truncate(MAX_CANDIDATES);
+            Completion { input, candidates }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::handle::test_handle;
+    use crate::http::Request;
+
+    #[test]
+    fn completes_commands() {
+        let completion = complete("re", [].into_iter(), [].into_iter());
+        assert_eq!(completion.input, "relations ");
+        let completion = complete("help de", [].into_iter(), [].into_iter());
+        assert_eq!(completion.input, "help description ");
+        let completion = complete("", [].into_iter(), [].into_iter());
+        assert_eq!(completion.candidates.len(), COMMANDS.len());
+    }
+
+    #[test]
+    fn completes_arguments() {
+        let handles = [test_handle(0xab), test_handle(0xac)];
+        let completion = complete("contents A", handles.iter(), [].into_iter());
+        assert_eq!(completion.input, "contents a");
+        assert_eq!(completion.candidates.len(), 2);
+        let completion = complete("contents ab", handles.iter(), [].into_iter());
+        assert_eq!(completion.input, format!("contents {} ", "ab".repeat(32)));
+        let completion = complete("relations ab e", handles.iter(), [].into_iter());
+        assert_eq!(completion.input, "relations ab eval ");
+        let completion = complete("blob ab 1", handles.iter(), [].into_iter());
+        assert_eq!(completion.input, "blob ab 1");
+        let completion = complete("tag ab; contents ab", handles.iter(), [].into_iter());
+        assert_eq!(
+            completion.input,
+            format!("tag ab; contents {} ", "ab".repeat(32))
+        );
+        let completion = complete("contents ab | relations e", handles.iter(), [].into_iter());
+        assert_eq!(completion.input, "contents ab | relations eval ");
+        let aliases = ["add-simple", "Input"];
+        let completion = complete("relations @a", handles.iter(), aliases.into_iter());
+        assert_eq!(completion.input, "relations @add-simple ");
+        let completion = complete("eval @", handles.iter(), aliases.into_iter());
+        assert_eq!(completion.candidates, ["@Input", "@add-simple"]);
+    }
+
+    #[test]
+    fn resolves_handle_arguments() {
+        use crate::graphs::{Relation, RelationRhs};
+
+        let mut graph = RelationStorage::default();
+        let tree = test_handle(0xab);
+        let entry = test_handle(1);
+        graph.insert(Relation::new(
+            tree.clone(),
+            RelationRhs::TreeEntry(entry, 0),
+        ));
+        assert_eq!(
+            resolve_handles("blob ABAB 10 20", false, &graph).unwrap(),
+            format!("blob {} 10 20", tree.to_hex())
+        );
+        assert_eq!(
+            resolve_handles("relations $_0 eval", false, &graph).unwrap(),
+            "relations $_0 eval"
+        );
+        // The 01 is the offset, after the piped handle.
+        assert_eq!(resolve_handles("blob 01", true, &graph).unwrap(), "blob 01");
+        assert!(resolve_handles("contents cdcd", false, &graph).is_err());
+    }
+
+    #[test]
+    fn helps_with_every_command() {
+        let handle = "ab".repeat(32);
+        for command in COMMANDS {
+            let text = help(&format!("help {}", command.name)).unwrap();
+            assert!(text.starts_with(command.name));
+            if command.name == "help" || command.name == "source" {
+                continue;
+            }
+            let args: Vec<String> = command
+                .args
+                .iter()
+                .map(|arg| match arg {
+                    Arg::Handle => handle.clone(),
+                    Arg::Op => "eval".to_owned(),
+                    Arg::Number(_) => "1".to_owned(),
+                    Arg::Command => "help".to_owned(),
+                    Arg::File => unreachable!(),
+                })
+                .collect();
+            let input = format!("{} {}", command.name, args.join(" "));
+            assert!(Request::from_cli(&input).is_ok(), "{input}");
+        }
+        let text = help("help").unwrap();
+        assert!(text.contains("blob <handle> [offset] [length]"));
+        assert!(text.contains("its first 4 or more"));
+        assert!(text.contains("@name"));
+        assert!(help("contents").is_none());
+    }
+}
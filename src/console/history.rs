@@ -0,0 +1,170 @@
+/// Commands typed into the console, oldest first, which are kept between
+/// sessions.
+#[derive(serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub(crate) struct History {
+    entries: Vec<String>,
+    /// The most commands kept.
+    pub max_len: usize,
+    /// The entry shown while moving through the history.
+    #[serde(skip)]
+    position: Option<usize>,
+    /// What was typed before moving through the history.
+    #[serde(skip)]
+    draft: String,
+    #[serde(skip)]
+    search: Option<Search>,
+}
+
+/// A reverse search through the history, like Ctrl+R in a shell.
+#[derive(Default)]
+pub(crate) struct Search {
+    pub query: String,
+    /// The index of the match shown.
+    position: Option<usize>,
+}
+
+impl Default for History {
+    fn default() -> Self {
+        Self {
+            entries: vec![],
+            max_len: 500,
+            position: None,
+            draft: String::new(),
+            search: None,
+        }
+    }
+}
+
+impl History {
+    /// Adds a command, unless it repeats the last one.
+    pub(crate) fn push(&mut self, command: &str) {
+        self.position = None;
+        let command = command.trim();
+        if command.is_empty() || self.entries.last().map(String::as_str) == Some(command) {
+            return;
+        }
+        self.entries.push(command.to_owned());
+        self.truncate();
+    }
+
+    /// Drops the oldest commands beyond `max_len`.
+    pub(crate) fn truncate(&mut self) {
+        let excess = self.entries.len().saturating_sub(self.max_len);
+        self.entries.drain(..excess);
+    }
+
+    /// Moves to the previous command, remembering `current` to come back to.
+    pub(crate) fn older(&mut self, current: &str) -> Option<String> {
+        let position = match self.position {
+            None if self.entries.is_empty() => return None,
+            None => {
+                self.draft = current.to_owned();
+                self.entries.len() - 1
+            }
+            Some(position) => position.checked_sub(1)?,
+        };
+        self.position = Some(position);
+        Some(self.entries[position].clone())
+    }
+
+    /// Moves to the next command, or back to what was being typed.
+    pub(crate) fn newer(&mut self) -> Option<String> {
+        let position = self.position? + 1;
+        if position < self.entries.len() {
+            self.position = Some(position);
+            Some(self.entries[position].clone())
+        } else {
+            self.position = None;
+            Some(std::mem::take(&mut self.draft))
+        }
+    }
+
+    pub(crate) fn search(&mut self) -> Option<&mut Search> {
+        self.search.as_mut()
+    }
+
+    pub(crate) fn start_search(&mut self) {
+        self.search = Some(Search::default());
+    }
+
+    pub(crate) fn stop_search(&mut self) {
+        self.search = None;
+    }
+
+    /// Finds the newest command containing the query, or the next older one
+    /// if `older` is set.
+    pub(crate) fn find(&mut self, older: bool) {
+        let Some(search) = &mut self.search else {
+            return;
+        };
+        let before = match (older, search.position) {
+            (true, Some(position)) => position,
+            _ => self.entries.len(),
+        };
+        let found = self.entries[..before]
+            .iter()
+            .rposition(|e| e.contains(&search.query));
+        // Keep showing the last match when there are no older ones.
+        if found.is_some() || !older {
+            search.position = found;
+        }
+    }
+
+    /// The command found by the search.
+    pub(crate) fn found(&self) -> Option<&str> {
+        let position = self.search.as_ref()?.position?;
+        Some(self.entries[position].as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn history(entries: &[&str]) -> History {
+        let mut history = History::default();
+        for entry in entries {
+            history.push(entry);
+        }
+        history
+    }
+
+    #[test]
+    fn navigates() {
+        let mut history = history(&["a", "b", "b", " ", "c"]);
+        assert_eq!(history.older("dr").as_deref(), Some("c"));
+        assert_eq!(history.older("c").as_deref(), Some("b"));
+        assert_eq!(history.older("b").as_deref(), Some("a"));
+        assert_eq!(history.older("a"), None);
+        assert_eq!(history.newer().as_deref(), Some("b"));
+        assert_eq!(history.newer().as_deref(), Some("c"));
+        assert_eq!(history.newer().as_deref(), Some("dr"));
+        assert_eq!(history.newer(), None);
+    }
+
+    #[test]
+    fn keeps_max_len() {
+        let mut history = history(&["a", "b", "c"]);
+        history.max_len = 2;
+        history.truncate();
+        history.push("d");
+        assert_eq!(history.entries, ["c", "d"]);
+    }
+
+    #[test]
+    fn searches_backwards() {
+        let mut history = history(&["contents x", "eval y", "contents z"]);
+        history.start_search();
+        history.search().unwrap().query = "contents".to_owned();
+        history.find(false);
+        assert_eq!(history.found(), Some("contents z"));
+        for _ in 0..2 {
+            history.find(true);
+            assert_eq!(history.found(), Some("contents x"));
+        }
+        history.search().unwrap().query = "nothing".to_owned();
+        history.find(false);
+        assert_eq!(history.found(), None);
+    }
+}
@@ -0,0 +1,505 @@
+use std::collections::{HashMap, VecDeque};
+
+use crate::{
+    bookmarks::Bookmarks,
+    graphs::{Relation, RelationStorage},
+    handle::Handle,
+    http::{HttpContext, HttpError, HttpLog, Request},
+};
+
+/// The most requests a script has in flight at once.
+const MAX_IN_FLIGHT: usize = 8;
+/// The most scripts which can be sourced inside each other.
+const MAX_SOURCE_DEPTH: usize = 16;
+
+/// Runs console input, which can be several commands separated by `;` or
+/// new lines, and pipelines separated by `|` which run each command on every
+/// handle the previous one returned. A pipeline stops at the first command
+/// with a failed request, while the pipelines after it still run.
+#[derive(Default)]
+pub(crate) struct Runner {
+    queue: VecDeque<Pipeline>,
+    stage: Option<Stage>,
+    /// The handles returned by the last command, which `$_` stands for.
+    last: Vec<Handle>,
+}
+
+/// A pipeline waiting to run.
+struct Pipeline {
+    commands: Vec<String>,
+    /// The scripts being sourced when the pipeline was read, outermost
+    /// first, so that scripts cannot source themselves.
+    sources: Vec<String>,
+}
+
+/// Where a [`Runner`] sends requests and writes output, which is the
+/// console's log in the viewer and the terminal in `fix-query`.
+pub(crate) trait Host {
+    /// Sends the request for `command`, whose result is passed to
+    /// [`Runner::on_received`] with the returned id.
+    fn send(&mut self, command: String) -> usize;
+    /// Shows text written for `command`, such as help.
+    fn print(&mut self, command: &str, text: String);
+    /// Reports that `command` could not run.
+    fn error(&mut self, command: &str, error: String);
+}
+
+/// Runs scripts in the viewer, writing to the console's log.
+pub(crate) struct LogHost<'a> {
+    pub ctx: &'a HttpContext,
+    pub log: &'a mut HttpLog,
+}
+
+/// A command of a pipeline which is running.
+struct Stage {
+    /// The command as it was typed, for error messages.
+    command: String,
+    /// Commands to send, with their index in `results`.
+    unsent: VecDeque<(usize, String)>,
+    /// Ids of requests in flight, with their index and what they asked for.
+    in_flight: HashMap<usize, (usize, Option<Request>)>,
+    /// The handles returned for each command, in the order they were sent.
+    results: Vec<Vec<Handle>>,
+    /// The number of requests which failed.
+    failed: usize,
+    /// The commands to run on the handles returned by this one.
+    rest: VecDeque<String>,
+}
+
+/// Splits a script into pipelines of commands, skipping blank lines and
+/// comments starting with `#`.
+pub(crate) fn parse(script: &str) -> Vec<Vec<String>> {
+    script
+        .lines()
+        .filter(|line| !line.trim_start().starts_with('#'))
+        .flat_map(|line| line.split(';'))
+        .filter(|pipeline| !pipeline.trim().is_empty())
+        .map(|pipeline| {
+            pipeline
+                .split('|')
+                .map(|command| command.trim().to_owned())
+                .collect()
+        })
+        .collect()
+}
+
+/// The handles a request returned, which are the ones on the other side of
+/// its relations from the handle it asked about.
+pub(crate) fn result_handles(request: &Request, relations: &[Relation]) -> Vec<Handle> {
+    relations
+        .iter()
+        .filter_map(|relation| {
+            if relation.lhs != *request.handle() {
+                return Some(relation.lhs.clone());
+            }
+            relation.rhs.get_port_type().map(|(_, handle)| handle)
+        })
+        .collect()
+}
+
+/// The commands to send for `command`, which runs once for every handle piped
+/// into it, with the handle as its first argument.
+pub(crate) fn expand(
+    command: &str,
+    piped: Option<&[Handle]>,
+    last: &[Handle],
+) -> Result<Vec<String>, String> {
+    let commands = substitute(command, last)?;
+    let Some(handles) = piped else {
+        return Ok(commands);
+    };
+    Ok(commands
+        .iter()
+        .flat_map(|command| {
+            let (name, args) = command.split_once(' ').unwrap_or((command, ""));
+            handles
+                .iter()
+                .map(move |h| format!("{name} {} {args}", h.to_hex()).trim().to_owned())
+        })
+        .collect())
+}
+
+/// Adds the handles returned by a command to `handles`, skipping those
+/// already in it.
+pub(crate) fn extend_unique(handles: &mut Vec<Handle>, new: impl IntoIterator<Item = Handle>) {
+    for handle in new {
+        if !handles.contains(&handle) {
+            handles.push(handle);
+        }
+    }
+}
+
+/// Expands `$_` to every handle of the last result, running the command once
+/// for each, and `$_0`, `$_1`... to one of them.
+fn substitute(command: &str, last: &[Handle]) -> Result<Vec<String>, String> {
+    let mut expanded = vec![String::new()];
+    for (i, word) in command.split_whitespace().enumerate() {
+        let choices = match word.strip_prefix("$_") {
+            None => vec![word.to_owned()],
+            Some("") => last.iter().map(Handle::to_hex).collect(),
+            Some(index) => {
+                let handle = index
+                    .parse::<usize>()
+                    .ok()
+                    .and_then(|index| last.get(index))
+                    .ok_or_else(|| {
+                        format!("{word} is not one of the {} last handles", last.len())
+                    })?;
+                vec![handle.to_hex()]
+            }
+        };
+        expanded = expanded
+            .iter()
+            .flat_map(|prefix| {
+                choices.iter().map(move |choice| match i {
+                    0 => choice.clone(),
+                    _ => format!("{prefix} {choice}"),
+                })
+            })
+            .collect();
+    }
+    Ok(expanded)
+}
+
+impl Host for LogHost<'_> {
+    fn send(&mut self, command: String) -> usize {
+        Request::parse_send(command, self.ctx.clone())
+    }
+
+    fn print(&mut self, command: &str, text: String) {
+        self.log.print(format!("> {command}\n{text}"));
+    }
+
+    fn error(&mut self, command: &str, error: String) {
+        self.log.print(format!("> {command}\n{error}"));
+    }
+}
+
+impl Runner {
+    /// Queues console input to run after anything already running.
+    pub(crate) fn run(&mut self, input: &str) {
+        self.queue
+            .extend(parse(input).into_iter().map(|commands| Pipeline {
+                commands,
+                sources: vec![],
+            }));
+    }
+
+    pub(crate) fn is_running(&self) -> bool {
+        self.stage.is_some() || !self.queue.is_empty()
+    }
+
+    pub(crate) fn cancel(&mut self) {
+        self.queue.clear();
+        self.stage = None;
+    }
+
+    /// The handles returned by the last command.
+    #[cfg(test)]
+    pub(crate) fn last(&self) -> &[Handle] {
+        &self.last
+    }
+
+    /// Starts commands and sends their requests while there is room.
+    /// Handles in commands are resolved with `bookmarks` and `graph`.
+    pub(crate) fn pump(
+        &mut self,
+        host: &mut impl Host,
+        bookmarks: &Bookmarks,
+        graph: &RelationStorage,
+    ) {
+        loop {
+            if self.stage.is_none() {
+                let Some(pipeline) = self.queue.pop_front() else {
+                    return;
+                };
+                if pipeline.commands.iter().any(String::is_empty) {
+                    let pipeline = pipeline.commands.join(" | ");
+                    host.error(&pipeline, "Missing command in pipeline".to_owned());
+                    continue;
+                }
+                let mut commands: VecDeque<String> = pipeline.commands.into();
+                let first = commands.pop_front().unwrap_or_default();
+                if first.split_whitespace().next() == Some("source") {
+                    self.source(&first, pipeline.sources, host);
+                } else {
+                    self.start(first, None, commands, host, bookmarks, graph);
+                }
+                continue;
+            }
+            let Some(stage) = &mut self.stage else {
+                return;
+            };
+            while stage.in_flight.len() < MAX_IN_FLIGHT {
+                let Some((index, command)) = stage.unsent.pop_front() else {
+                    break;
+                };
+                let request = Request::from_cli(&command).ok();
+                let id = host.send(command);
+                stage.in_flight.insert(id, (index, request));
+            }
+            if !stage.unsent.is_empty() || !stage.in_flight.is_empty() {
+                return;
+            }
+            // Every request of the command has completed.
+            let stage = self.stage.take().unwrap();
+            let mut handles = vec![];
+            extend_unique(&mut handles, stage.results.into_iter().flatten());
+            self.last = handles.clone();
+            let mut rest = stage.rest;
+            if stage.failed > 0 && !rest.is_empty() {
+                host.error(
+                    &stage.command,
+                    format!("{} requests failed, so the pipeline stopped", stage.failed),
+                );
+                continue;
+            }
+            if let Some(next) = rest.pop_front() {
+                self.start(next, Some(handles), rest, host, bookmarks, graph);
+            }
+        }
+    }
+
+    /// Records the handles returned to one of the script's requests.
+    pub(crate) fn on_received(&mut self, id: usize, result: &Result<Vec<Relation>, HttpError>) {
+        let Some(stage) = &mut self.stage else {
+            return;
+        };
+        let Some((index, request)) = stage.in_flight.remove(&id) else {
+            return;
+        };
+        match (request, result) {
+            (Some(request), Ok(relations)) => {
+                stage.results[index] = result_handles(&request, relations);
+            }
+            _ => stage.failed += 1,
+        }
+    }
+
+    /// Starts running a command on the handles piped into it, if any.
+    fn start(
+        &mut self,
+        command: String,
+        piped: Option<Vec<Handle>>,
+        rest: VecDeque<String>,
+        host: &mut impl Host,
+        bookmarks: &Bookmarks,
+        graph: &RelationStorage,
+    ) {
+        if let Some(help) = super::help(&command) {
+            host.print(&command, help);
+            return;
+        }
+
+        let commands = bookmarks
+            .expand(&command)
+            .and_then(|aliased| super::resolve_handles(&aliased, piped.is_some(), graph))
+            .map_err(|e| format!("{e:#}"))
+            .and_then(|resolved| expand(&resolved, piped.as_deref(), &self.last));
+        let commands = match commands {
+            Ok(commands) => commands,
+            Err(e) => {
+                host.error(&command, e);
+                return;
+            }
+        };
+        self.stage = Some(Stage {
+            command,
+            results: vec![vec![]; commands.len()],
+            unsent: commands.into_iter().enumerate().collect(),
+            in_flight: HashMap::new(),
+            failed: 0,
+            rest,
+        });
+    }
+
+    /// Runs a script before the rest of the input, unless it is already
+    /// being sourced, which would never end.
+    fn source(&mut self, command: &str, mut sources: Vec<String>, host: &mut impl Host) {
+        let Some(path) = command.split_whitespace().nth(1) else {
+            host.error(command, "Expected a file to source".to_owned());
+            return;
+        };
+        if sources.iter().any(|source| source == path) {
+            host.error(command, format!("{path} is already being sourced"));
+            return;
+        }
+        if sources.len() >= MAX_SOURCE_DEPTH {
+            let error = format!("Scripts can only be sourced {MAX_SOURCE_DEPTH} deep");
+            host.error(command, error);
+            return;
+        }
+        let script = match read_script(path) {
+            Ok(script) => script,
+            Err(e) => {
+                host.error(command, format!("{e:#}"));
+                return;
+            }
+        };
+        sources.push(path.to_owned());
+        for commands in parse(&script).into_iter().rev() {
+            self.queue.push_front(Pipeline {
+                commands,
+                sources: sources.clone(),
+            });
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn read_script(path: &str) -> anyhow::Result<String> {
+    crate::files::read(path)
+}
+
+#[cfg(target_arch = "wasm32")]
+pub(crate) fn read_script(_path: &str) -> anyhow::Result<String> {
+    anyhow::bail!("Scripts can only be sourced in native builds")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{graphs::RelationRhs, handle::test_handle};
+
+    /// Records what the runner sends, without answering.
+    #[derive(Default)]
+    struct RecordingHost {
+        sent: Vec<String>,
+        errors: Vec<String>,
+    }
+
+    impl Host for RecordingHost {
+        fn send(&mut self, command: String) -> usize {
+            self.sent.push(command);
+            self.sent.len() - 1
+        }
+
+        fn print(&mut self, _command: &str, _text: String) {}
+
+        fn error(&mut self, command: &str, _error: String) {
+            self.errors.push(command.to_owned());
+        }
+    }
+
+    #[test]
+    fn stops_pipelines_at_failures() {
+        let tree = test_handle(1);
+        let failure = Err(HttpError {
+            id: 0,
+            kind: crate::http::ErrorKind::Timeout,
+        });
+        let mut runner = Runner::default();
+        let mut host = RecordingHost::default();
+        let (bookmarks, graph) = (Bookmarks::default(), RelationStorage::default());
+        runner.run(&format!(
+            "contents {} | eval; tag {}",
+            tree.to_hex(),
+            tree.to_hex()
+        ));
+        runner.pump(&mut host, &bookmarks, &graph);
+        assert_eq!(host.sent.len(), 1);
+
+        runner.on_received(0, &failure);
+        runner.pump(&mut host, &bookmarks, &graph);
+        // The eval is skipped, and the next pipeline runs.
+        assert_eq!(host.errors.len(), 1);
+        assert_eq!(host.sent.len(), 2);
+        assert!(host.sent[1].starts_with("tag"));
+
+        runner.on_received(1, &Ok(vec![]));
+        runner.pump(&mut host, &bookmarks, &graph);
+        assert_eq!(host.errors.len(), 1);
+        assert!(!runner.is_running());
+        runner.run("contents |");
+        runner.pump(&mut host, &bookmarks, &graph);
+        assert_eq!(host.errors.len(), 2);
+    }
+
+    #[test]
+    fn stops_recursive_sources() {
+        let dir = std::env::temp_dir().join(format!("fix-viewer-sources-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = |name: &str| dir.join(name).to_string_lossy().into_owned();
+        let tag = format!("tag {}", test_handle(1).to_hex());
+        std::fs::write(path("self"), format!("{tag}\nsource {}", path("self"))).unwrap();
+        std::fs::write(path("a"), format!("source {}", path("b"))).unwrap();
+        std::fs::write(path("b"), format!("source {}", path("a"))).unwrap();
+
+        let mut runner = Runner::default();
+        let mut host = RecordingHost::default();
+        let (bookmarks, graph) = (Bookmarks::default(), RelationStorage::default());
+        runner.run(&format!("source {}; source {}", path("self"), path("a")));
+        runner.pump(&mut host, &bookmarks, &graph);
+        assert_eq!(host.sent, [tag]);
+        runner.on_received(0, &Ok(vec![]));
+        runner.pump(&mut host, &bookmarks, &graph);
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(host.sent.len(), 1);
+        assert_eq!(host.errors.len(), 2);
+        assert!(!runner.is_running());
+    }
+
+    #[test]
+    fn parses_sequences_and_pipelines() {
+        let script = "contents a | eval; tag b\n# comment\n\ndescription c |  eval ";
+        assert_eq!(
+            parse(script),
+            vec![
+                vec!["contents a".to_owned(), "eval".to_owned()],
+                vec!["tag b".to_owned()],
+                vec!["description c".to_owned(), "eval".to_owned()],
+            ]
+        );
+    }
+
+    #[test]
+    fn substitutes_last_result() {
+        let last = [test_handle(1), test_handle(2)];
+        assert_eq!(
+            substitute("eval $_", &last).unwrap(),
+            vec![
+                format!("eval {}", test_handle(1).to_hex()),
+                format!("eval {}", test_handle(2).to_hex())
+            ]
+        );
+        assert_eq!(
+            substitute("relations $_1 apply", &last).unwrap(),
+            vec![format!("relations {} apply", test_handle(2).to_hex())]
+        );
+        assert!(substitute("eval $_2", &last).is_err());
+    }
+
+    #[test]
+    fn expands_piped_handles() {
+        let piped = [test_handle(3), test_handle(4)];
+        assert_eq!(
+            expand("relations apply", Some(&piped), &[]).unwrap(),
+            vec![
+                format!("relations {} apply", test_handle(3).to_hex()),
+                format!("relations {} apply", test_handle(4).to_hex())
+            ]
+        );
+        assert!(expand("eval", Some(&[]), &[]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn returns_handles_across_relations() {
+        let tree = test_handle(1);
+        let contents = [
+            Relation::new(tree.clone(), RelationRhs::TreeEntry(test_handle(2), 0)),
+            Relation::new(tree.clone(), RelationRhs::TreeEntry(test_handle(3), 1)),
+        ];
+        assert_eq!(
+            result_handles(&Request::Contents(tree.clone()), &contents),
+            vec![test_handle(2), test_handle(3)]
+        );
+        let explanations = [Relation::new(
+            test_handle(4),
+            RelationRhs::Eval(tree.clone()),
+        )];
+        assert_eq!(
+            result_handles(&Request::Explanations(tree), &explanations),
+            vec![test_handle(4)]
+        );
+    }
+}
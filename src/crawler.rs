@@ -0,0 +1,290 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use egui::{DragValue, Label, ProgressBar, Ui};
+
+use crate::{
+    graphs::{Relation, RelationRhs, RelationStorage},
+    handle::{ContentType, Handle, Operation},
+    http::{HttpContext, HttpError, Request},
+};
+
+/// The most requests a crawl has in flight at once.
+const MAX_IN_FLIGHT: usize = 8;
+
+/// Expands the graph around a handle by following contents, eval and apply
+/// relations up to some depth.
+pub(crate) struct Crawler {
+    pub depth: usize,
+    pub explanations: bool,
+    crawl: Option<Crawl>,
+}
+
+struct Crawl {
+    root: Handle,
+    max_depth: usize,
+    explanations: bool,
+    seen: HashSet<Handle>,
+    /// Requests waiting to be sent, with the depth of their handle.
+    queue: VecDeque<(Request, usize)>,
+    /// Ids of requests in flight, with the depth of their handle.
+    in_flight: HashMap<usize, usize>,
+    completed: usize,
+    failed: usize,
+}
+
+impl Default for Crawler {
+    fn default() -> Self {
+        Self {
+            depth: 2,
+            explanations: false,
+            crawl: None,
+        }
+    }
+}
+
+impl Crawler {
+    /// Starts crawling from `root`, cancelling any crawl in progress.
+    pub(crate) fn start(&mut self, root: Handle) {
+        let mut crawl = Crawl {
+            root: root.clone(),
+            max_depth: self.depth,
+            explanations: self.explanations,
+            seen: HashSet::new(),
+            queue: VecDeque::new(),
+            in_flight: HashMap::new(),
+            completed: 0,
+            failed: 0,
+        };
+        crawl.visit(root, 0);
+        self.crawl = Some(crawl);
+    }
+
+    /// Stops sending requests. Responses to requests in flight still arrive.
+    pub(crate) fn cancel(&mut self) {
+        self.crawl = None;
+    }
+
+    /// Sends queued requests while there is room. Requests `graph` already
+    /// answers come back without going to the server.
+    pub(crate) fn pump(&mut self, ctx: &HttpContext, graph: &RelationStorage) {
+        let Some(crawl) = &mut self.crawl else {
+            return;
+        };
+        while crawl.in_flight.len() < MAX_IN_FLIGHT {
+            let Some((request, depth)) = crawl.queue.pop_front() else {
+                break;
+            };
+            let id = request.send_unless_known(ctx.clone(), graph);
+            crawl.in_flight.insert(id, depth);
+        }
+    }
+
+    /// Follows the relations from a response to one of the crawl's requests.
+    pub(crate) fn on_received(&mut self, id: usize, result: &Result<Vec<Relation>, HttpError>) {
+        let Some(crawl) = &mut self.crawl else {
+            return;
+        };
+        let Some(depth) = crawl.in_flight.remove(&id) else {
+            return;
+        };
+        crawl.completed += 1;
+        let relations = match result {
+            Ok(relations) => relations,
+            Err(_) => {
+                crawl.failed += 1;
+                return;
+            }
+        };
+        // The root is at depth 0, so its relations make up the first level.
+        if depth + 1 >= crawl.max_depth {
+            return;
+        }
+        for relation in relations {
+            match &relation.rhs {
+                RelationRhs::TreeEntry(h, _) | RelationRhs::Eval(h) | RelationRhs::Apply(h) => {
+                    crawl.visit(relation.lhs.clone(), depth + 1);
+                    crawl.visit(h.clone(), depth + 1);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn is_done(&self) -> bool {
+        self.crawl
+            .as_ref()
+            .map_or(true, |c| c.queue.is_empty() && c.in_flight.is_empty())
+    }
+
+    pub(crate) fn ui(&mut self, ui: &mut Ui) {
+        ui.heading("Expand");
+        ui.horizontal(|ui| {
+            ui.label("Levels:");
+            ui.add(DragValue::new(&mut self.depth).clamp_range(1..=16));
+            ui.checkbox(&mut self.explanations, "explanations");
+        });
+        let done = self.is_done();
+        if let Some(crawl) = &self.crawl {
+            let total = crawl.completed + crawl.in_flight.len() + crawl.queue.len();
+            ui.add(
+                ProgressBar::new(crawl.completed as f32 / total.max(1) as f32).text(format!(
+                    "{}/{} requests, {} failed",
+                    crawl.completed, total, crawl.failed
+                )),
+            );
+            ui.add(Label::new(format!("from {}", crawl.root.to_hex())).truncate(true));
+        }
+        if !done && ui.button("cancel").clicked() {
+            self.cancel();
+        }
+    }
+}
+
+impl Crawl {
+    fn visit(&mut self, handle: Handle, depth: usize) {
+        if !self.seen.insert(handle.clone()) {
+            return;
+        }
+        // Literals have no contents and evaluate to themselves.
+        if handle.is_literal() {
+            return;
+        }
+        match handle.get_content_type() {
+            ContentType::Tree | ContentType::Thunk => self
+                .queue
+                .push_back((Request::Contents(handle.clone()), depth)),
+            ContentType::Tag => self
+                .queue
+                .push_back((Request::TagContents(handle.clone()), depth)),
+            ContentType::Blob => {}
+        }
+        for op in [Operation::Eval, Operation::Apply] {
+            self.queue
+                .push_back((Request::Relations(handle.clone(), op), depth));
+        }
+        if self.explanations {
+            self.queue
+                .push_back((Request::Explanations(handle.clone()), depth));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc, Arc,
+    };
+
+    use super::*;
+    use crate::{
+        handle::test_handle,
+        http::{ErrorKind, HttpMessage, RequestCache, Session},
+    };
+
+    fn tree(i: u8) -> Handle {
+        let mut handle = test_handle(i);
+        handle.content[31] = 0x40;
+        handle
+    }
+
+    /// A context whose requests fail at once without a server, since the
+    /// tests hand the crawler its responses themselves.
+    fn context() -> (HttpContext, mpsc::Receiver<HttpMessage>) {
+        let (tx, rx) = mpsc::channel();
+        let ctx = HttpContext {
+            client: Arc::new(reqwest::Client::new()),
+            egui_ctx: egui::Context::default(),
+            url_base: String::new(),
+            tx,
+            counter: Arc::new(AtomicUsize::new(0)),
+            cache: Arc::new(RequestCache::default()),
+            session: Arc::new(Session::default()),
+            offline: true,
+        };
+        (ctx, rx)
+    }
+
+    fn crawl(crawler: &Crawler) -> &Crawl {
+        crawler.crawl.as_ref().unwrap()
+    }
+
+    /// The contents of `root`, with `entries` trees.
+    fn contents(root: &Handle, entries: u8) -> Result<Vec<Relation>, HttpError> {
+        Ok((0..entries)
+            .map(|i| Relation::new(root.clone(), RelationRhs::TreeEntry(tree(10 + i), i.into())))
+            .collect())
+    }
+
+    #[test]
+    fn limits_depth() {
+        let (ctx, _rx) = context();
+        let graph = RelationStorage::default();
+        let mut crawler = Crawler {
+            depth: 1,
+            ..Crawler::default()
+        };
+        crawler.start(tree(1));
+        crawler.pump(&ctx, &graph);
+        // Contents, eval and apply of the root, in that order.
+        assert_eq!(crawl(&crawler).in_flight.len(), 3);
+        crawler.on_received(0, &contents(&tree(1), 2));
+        assert!(crawl(&crawler).queue.is_empty());
+
+        crawler.depth = 2;
+        crawler.start(tree(1));
+        crawler.pump(&ctx, &graph);
+        crawler.on_received(3, &contents(&tree(1), 2));
+        assert_eq!(crawl(&crawler).queue.len(), 6);
+        crawler.pump(&ctx, &graph);
+        crawler.on_received(4, &Ok(vec![]));
+        crawler.on_received(5, &Ok(vec![]));
+        // Entries are at the last level, so their relations are not followed.
+        for id in 6..12 {
+            crawler.on_received(id, &contents(&tree(20), 2));
+        }
+        assert!(crawl(&crawler).queue.is_empty());
+        assert_eq!(crawl(&crawler).completed, 9);
+        assert!(crawler.is_done());
+    }
+
+    #[test]
+    fn bounds_requests_in_flight() {
+        let (ctx, _rx) = context();
+        let graph = RelationStorage::default();
+        let mut crawler = Crawler::default();
+        crawler.start(tree(1));
+        crawler.pump(&ctx, &graph);
+        crawler.on_received(0, &contents(&tree(1), 5));
+        crawler.pump(&ctx, &graph);
+        assert_eq!(crawl(&crawler).in_flight.len(), MAX_IN_FLIGHT);
+        // Eval and apply of the root are still in flight.
+        assert_eq!(crawl(&crawler).queue.len(), 15 - (MAX_IN_FLIGHT - 2));
+        crawler.on_received(
+            1,
+            &Err(HttpError {
+                id: 1,
+                kind: ErrorKind::Timeout,
+            }),
+        );
+        assert_eq!(crawl(&crawler).failed, 1);
+        crawler.pump(&ctx, &graph);
+        assert_eq!(crawl(&crawler).in_flight.len(), MAX_IN_FLIGHT);
+    }
+
+    #[test]
+    fn cancels() {
+        let (ctx, _rx) = context();
+        let graph = RelationStorage::default();
+        let mut crawler = Crawler::default();
+        crawler.start(tree(1));
+        crawler.pump(&ctx, &graph);
+        crawler.cancel();
+        assert!(crawler.is_done());
+        // Responses to requests in flight are ignored.
+        crawler.on_received(0, &contents(&tree(1), 2));
+        crawler.pump(&ctx, &graph);
+        assert!(crawler.crawl.is_none());
+        assert_eq!(ctx.counter.load(Ordering::SeqCst), 3);
+    }
+}
@@ -0,0 +1,60 @@
+use anyhow::Result;
+
+/// Saves text to a file. Natively `name` is a path, while on the web the
+/// browser downloads a file called `name`.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn save(name: &str, contents: &str) -> Result<()> {
+    use anyhow::Context;
+
+    std::fs::write(name, contents).with_context(|| format!("writing {name}"))
+}
+
+/// Saves text to a file. Natively `name` is a path, while on the web the
+/// browser downloads a file called `name`.
+#[cfg(target_arch = "wasm32")]
+pub(crate) fn save(name: &str, contents: &str) -> Result<()> {
+    use anyhow::{anyhow, Context};
+    use wasm_bindgen::JsCast;
+
+    let mut url = "data:text/plain;charset=utf-8,".to_owned();
+    for byte in contents.bytes() {
+        if byte.is_ascii_alphanumeric() || b"-_.~".contains(&byte) {
+            url.push(byte as char);
+        } else {
+            url.push_str(&format!("%{byte:02X}"));
+        }
+    }
+    let document = web_sys::window()
+        .and_then(|w| w.document())
+        .context("no document")?;
+    let anchor: web_sys::HtmlAnchorElement = document
+        .create_element("a")
+        .map_err(|e| anyhow!("{e:?}"))?
+        .dyn_into()
+        .map_err(|e| anyhow!("{e:?}"))?;
+    anchor.set_href(&url);
+    anchor.set_download(name);
+    anchor.click();
+    Ok(())
+}
+
+/// Reads text from a file.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn read(path: &str) -> Result<String> {
+    use anyhow::Context;
+
+    std::fs::read_to_string(path).with_context(|| format!("reading {path}"))
+}
+
+/// Reads text from a file dropped onto the window, which is only available
+/// as bytes on the web.
+pub(crate) fn read_dropped(file: &egui::DroppedFile) -> Result<String> {
+    if let Some(bytes) = &file.bytes {
+        return String::from_utf8(bytes.to_vec()).map_err(Into::into);
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    if let Some(path) = &file.path {
+        return read(&path.to_string_lossy());
+    }
+    anyhow::bail!("could not read dropped file {}", file.name)
+}
@@ -1,16 +1,22 @@
+pub(crate) mod blob;
+pub(crate) mod export;
+pub(crate) mod layout;
+pub(crate) mod persist;
+
 use std::{
     borrow::Cow,
     collections::{BTreeSet, HashMap, HashSet, VecDeque},
     fmt::Display,
 };
 
+use anyhow::{bail, ensure, Result};
 use egui::{
     emath::TSTransform, epaint::CubicBezierShape, Color32, Grid, Id, InnerResponse, Label, Layout,
     Margin, Pos2, Rect, RichText, Sense, Stroke, TextStyle, Ui, Vec2,
 };
 
 use crate::{
-    handle::{Handle, Operation},
+    handle::{ContentType, Handle, Literal, Operation, HEX_LENGTH},
     http::{HttpContext, Request},
 };
 
@@ -37,16 +43,141 @@ impl RelationStorage {
             | RelationRhs::TreeEntry(h, _) => {
                 self.backward.entry(h.clone()).or_default().insert(relation);
             }
-            RelationRhs::Description(_) => {}
+            RelationRhs::Description(_) | RelationRhs::BlobChunk(..) => {}
+        }
+    }
+
+    /// The label of a tag, if its contents have been fetched.
+    pub(crate) fn get_tag_label(&self, tag: &Handle) -> Option<String> {
+        self.forward.get(tag)?.iter().find_map(|r| match &r.rhs {
+            RelationRhs::TagLabel(label) => Some(match label.literal() {
+                Some(Literal::String(s)) => s,
+                Some(literal) => literal.to_string(),
+                None => label.to_hex(),
+            }),
+            _ => None,
+        })
+    }
+
+    /// Every handle in the graph, some more than once.
+    pub(crate) fn handles(&self) -> impl Iterator<Item = &Handle> {
+        self.forward.keys().chain(self.backward.keys())
+    }
+
+    /// Parses a handle from its hex, or from the start of it if only one
+    /// handle in the graph starts that way, like git's short hashes.
+    pub(crate) fn resolve(&self, hex: &str) -> Result<Handle> {
+        /// The fewest hex characters a handle can be shortened to.
+        const MIN_PREFIX_LENGTH: usize = 4;
+        /// The most candidates listed when a prefix is ambiguous.
+        const MAX_CANDIDATES: usize = 10;
+
+        let hex = hex.trim();
+        ensure!(
+            hex.bytes().all(|b| b.is_ascii_hexdigit()),
+            "handle must be hex characters"
+        );
+        if hex.len() >= HEX_LENGTH {
+            return Handle::from_hex(hex);
+        }
+        ensure!(
+            hex.len() >= MIN_PREFIX_LENGTH,
+            "handle must be at least {MIN_PREFIX_LENGTH} hex characters"
+        );
+        // Handles can be listed more than once.
+        let candidates: BTreeSet<&Handle> =
+            self.handles().filter(|h| h.has_hex_prefix(hex)).collect();
+        let candidates: Vec<&Handle> = candidates.into_iter().collect();
+        match candidates.as_slice() {
+            [] => bail!("no known handle starts with {hex}"),
+            [only] => Ok((*only).clone()),
+            _ => {
+                let mut message = format!("{hex} could be any of {} handles:", candidates.len());
+                for candidate in candidates.iter().take(MAX_CANDIDATES) {
+                    message += &format!("\n{}", candidate.to_hex());
+                }
+                if candidates.len() > MAX_CANDIDATES {
+                    message += "\n...";
+                }
+                bail!(message)
+            }
+        }
+    }
+
+    /// The answer to a request that is already in the graph. Only requests
+    /// whose response can't change are answered, and only by relations they
+    /// return, so an empty tree or a missing description is still fetched.
+    pub(crate) fn answer(&self, request: &Request) -> Option<Vec<Relation>> {
+        if request.can_change() {
+            return None;
+        }
+        let relations = self.forward.get(request.handle())?.iter();
+        let answer: Vec<Relation> = relations
+            .filter(|relation| match (request, &relation.rhs) {
+                (Request::BlobContents(_, offset, _), RelationRhs::BlobChunk(at, _)) => {
+                    at == offset
+                }
+                (request, rhs) => matches!(
+                    (request, rhs),
+                    (Request::Relations(_, Operation::Eval), RelationRhs::Eval(_))
+                        | (
+                            Request::Relations(_, Operation::Apply),
+                            RelationRhs::Apply(_)
+                        )
+                        | (Request::Description(_), RelationRhs::Description(_))
+                        | (Request::Contents(_), RelationRhs::TreeEntry(..))
+                        | (
+                            Request::TagContents(_),
+                            RelationRhs::TagTarget(_)
+                                | RelationRhs::TagAuthor(_)
+                                | RelationRhs::TagLabel(_)
+                        )
+                ),
+            })
+            .cloned()
+            .collect();
+        let complete = match request {
+            // A tag's target, author and label.
+            Request::TagContents(_) => answer.len() == 3,
+            _ => !answer.is_empty(),
+        };
+        complete.then_some(answer)
+    }
+
+    /// The relations followed from `from` to reach `to` when visiting the
+    /// graph from `from`, or `None` if `to` cannot be reached.
+    pub(crate) fn path(&self, from: &Handle, to: &Handle) -> Option<Vec<Relation>> {
+        // Each handle reached, with the relation it was reached through.
+        let mut reached: HashMap<Handle, &Relation> = HashMap::new();
+        self.visit_bfs(from.clone(), |relation| {
+            let rhs = relation.rhs.get_port_type().map(|(_, h)| h);
+            // One end of the relation was reached before, the other is new.
+            let new = match rhs {
+                Some(rhs) if rhs != *from && !reached.contains_key(&rhs) => rhs,
+                _ => relation.lhs.clone(),
+            };
+            reached.entry(new).or_insert(relation);
+        });
+        let mut path = vec![];
+        let mut current = to.clone();
+        while current != *from {
+            let relation = *reached.get(&current)?;
+            current = match relation.rhs.get_port_type() {
+                Some((_, rhs)) if relation.lhs == current => rhs,
+                _ => relation.lhs.clone(),
+            };
+            path.push(relation.clone());
         }
+        path.reverse();
+        Some(path)
     }
 
-    pub(crate) fn visit_bfs(&self, root: Handle, mut handle: impl FnMut(&Relation)) {
-        fn handle_relations(
-            relations: &BTreeSet<Relation>,
+    pub(crate) fn visit_bfs<'a>(&'a self, root: Handle, mut handle: impl FnMut(&'a Relation)) {
+        fn handle_relations<'a>(
+            relations: &'a BTreeSet<Relation>,
             to_visit: &mut VecDeque<Handle>,
             seen: &mut HashSet<Handle>,
-            handle: &mut impl FnMut(&Relation),
+            handle: &mut impl FnMut(&'a Relation),
             selector: impl Fn(&Relation) -> Option<Handle>,
         ) {
             for relation in relations {
@@ -78,7 +209,9 @@ impl RelationStorage {
 }
 
 /// Information related to Handles that we have obtained from the API.
-#[derive(Hash, PartialEq, Eq, Clone, Debug, PartialOrd, Ord)]
+#[derive(
+    Hash, PartialEq, Eq, Clone, Debug, PartialOrd, Ord, serde::Deserialize, serde::Serialize,
+)]
 pub(crate) struct Relation {
     pub(crate) lhs: Handle,
     pub(crate) rhs: RelationRhs,
@@ -93,7 +226,9 @@ impl Relation {
 // For now. Should add content, tag.
 /// The order of these fields dictates the order in which they show up in the
 /// visualization windows.
-#[derive(Hash, PartialEq, Eq, Clone, Debug, PartialOrd, Ord)]
+#[derive(
+    Hash, PartialEq, Eq, Clone, Debug, PartialOrd, Ord, serde::Deserialize, serde::Serialize,
+)]
 pub enum RelationRhs {
     Eval(Handle),
     Apply(Handle),
@@ -103,10 +238,12 @@ pub enum RelationRhs {
     TagLabel(Handle),
     TreeEntry(Handle, usize),
     Description(String),
+    /// Bytes of a blob starting at an offset.
+    BlobChunk(u64, Vec<u8>),
 }
 
 impl RelationRhs {
-    fn get_abbrev(&self) -> Cow<str> {
+    pub(crate) fn get_abbrev(&self) -> Cow<'_, str> {
         match self {
             Self::Eval(_) => Cow::Borrowed("evaluates into"),
             Self::Apply(_) => Cow::Borrowed("applies into"),
@@ -116,6 +253,11 @@ impl RelationRhs {
             Self::TagLabel(_) => Cow::Borrowed("with this label"),
             Self::TreeEntry(_, i) => Cow::Owned(format!("has entry at index [{}]", i)),
             Self::Description(s) => Cow::Borrowed(s.as_str()),
+            Self::BlobChunk(offset, data) => Cow::Owned(format!(
+                "has bytes [{}..{}]",
+                offset,
+                offset + data.len() as u64
+            )),
         }
     }
 }
@@ -133,6 +275,11 @@ impl Display for RelationRhs {
                 f.write_fmt(format_args!("has entry {} at index [{}]", h.to_hex(), i))
             }
             Self::Description(s) => f.write_fmt(format_args!("{}", s.as_str())),
+            Self::BlobChunk(offset, data) => f.write_fmt(format_args!(
+                "has bytes [{}..{}]",
+                offset,
+                offset + data.len() as u64
+            )),
         }
     }
 }
@@ -147,7 +294,7 @@ impl RelationRhs {
             RelationRhs::TagTarget(h) => Some((PortType::TagTarget, h)),
             RelationRhs::TagLabel(h) => Some((PortType::TagLabel, h)),
             RelationRhs::TreeEntry(h, i) => Some((PortType::TreeEntry(i), h)),
-            RelationRhs::Description(_) => None,
+            RelationRhs::Description(_) | RelationRhs::BlobChunk(..) => None,
         }
     }
 }
@@ -180,6 +327,29 @@ impl PortType {
 pub(crate) struct Ports {
     pub input: Pos2,
     pub outputs: HashMap<PortType, Pos2>,
+    /// Whether the user is moving the node by hand.
+    pub dragged: bool,
+    /// Whether the user asked to crawl the graph from the node.
+    pub expand: bool,
+    /// Whether the user asked to see the notifications about the node.
+    pub show_notifications: bool,
+    /// Whether the user asked to make the node the target.
+    pub inspect: bool,
+    /// Whether the target field was left, with Enter or by clicking elsewhere.
+    pub submitted: bool,
+}
+
+/// How a node is drawn.
+#[derive(Clone)]
+pub(crate) struct NodeOptions {
+    pub pos: Pos2,
+    /// Move the node to `pos` even if it has already been placed.
+    pub reset: bool,
+    pub selected: bool,
+    /// The number of notifications about the node, such as failed requests.
+    pub notifications: usize,
+    /// The name the handle is bookmarked as.
+    pub alias: Option<String>,
 }
 
 #[derive(Clone)]
@@ -192,9 +362,9 @@ fn add_object(
     ctx: &egui::Context,
     window_id: impl std::hash::Hash,
     handle: Handle,
-    start_pos: Pos2,
+    options: NodeOptions,
     forward_relations: Option<&BTreeSet<Relation>>,
-    add_contents: impl FnOnce(&mut Ui) -> f32,
+    add_contents: impl FnOnce(&mut Ui) -> (f32, bool),
     clip: TransformClip,
 ) -> Ports {
     fn add_dot(ui: &mut Ui, center: Pos2) {
@@ -206,23 +376,34 @@ fn add_object(
             .circle(center, 4.0, Color32::WHITE, Stroke::NONE);
     }
 
-    fn main_body<'a>(
+    fn main_body(
         ui: &mut Ui,
         handle: Handle,
-        add_contents: impl FnOnce(&mut Ui) -> f32,
-        forward_relations: Option<&'a BTreeSet<Relation>>,
-    ) -> HashMap<PortType, f32> {
-        ui.add(Label::new(
-            // TODO handle more information
-            RichText::new(format!("{}", handle.to_hex()))
-                .text_style(TextStyle::Button)
-                .color(ui.style().visuals.strong_text_color()),
-        ));
-        add_contents(ui);
+        alias: Option<String>,
+        add_contents: impl FnOnce(&mut Ui) -> (f32, bool),
+        forward_relations: Option<&BTreeSet<Relation>>,
+    ) -> (HashMap<PortType, f32>, bool) {
+        if let Some(alias) = alias {
+            ui.label(
+                RichText::new(format!("@{alias}"))
+                    .text_style(TextStyle::Heading)
+                    .color(ui.style().visuals.strong_text_color()),
+            );
+        }
+        // TODO handle more information
+        let title = RichText::new(handle.short_hex())
+            .text_style(TextStyle::Button)
+            .color(ui.style().visuals.strong_text_color());
+        add_copyable_handle(ui, &handle, title);
+        let (_, expand) = add_contents(ui);
         ui.separator();
         let mut ports = HashMap::new();
         if let Some(relations) = forward_relations {
             for relation in relations {
+                // Shown by the blob inspector instead.
+                if let RelationRhs::BlobChunk(..) = relation.rhs {
+                    continue;
+                }
                 // Sorted by relation type.
                 let start_height = ui.min_rect().bottom();
                 ui.label(relation.rhs.get_abbrev());
@@ -233,33 +414,60 @@ fn add_object(
                 }
             }
         }
-        ports
+        (ports, expand)
     }
 
     // Note that the window_id should not be derived from the handle.
     // This allows the "main" window with an editable handle to not
     // jump around while the user types into it.
 
-    let v = egui::containers::Area::new(Id::new(window_id))
-        .default_pos(start_pos)
+    let mut area = egui::containers::Area::new(Id::new(window_id)).default_pos(options.pos);
+    if options.reset {
+        area = area.current_pos(options.pos);
+    }
+    let v = area
         .movable(true)
         .order(egui::Order::Foreground)
         .show(ctx, |ui| {
             ui.set_clip_rect(clip.transform.inverse() * clip.rect);
             ui.with_layout(Layout::default().with_main_wrap(false), |ui| {
                 // ui.style_mut().wrap = Some(false);
-                let InnerResponse { inner, response } = egui::Frame::default()
+                let InnerResponse {
+                    inner: ((inner, expand), show_notifications),
+                    response,
+                } = egui::Frame::default()
                     .rounding(egui::Rounding::same(4.0))
                     .inner_margin(Margin::same(8.0))
-                    .stroke(ctx.style().visuals.window_stroke)
+                    .stroke(if options.selected {
+                        ctx.style().visuals.selection.stroke
+                    } else {
+                        ctx.style().visuals.window_stroke
+                    })
                     .fill(ui.style().visuals.panel_fill)
                     .show(ui, |ui| {
-                        egui::containers::Resize::default()
+                        let show_notifications = options.notifications > 0
+                            && ui
+                                .button(
+                                    RichText::new(format!(
+                                        "⚠ {} notifications",
+                                        options.notifications
+                                    ))
+                                    .color(ui.visuals().warn_fg_color),
+                                )
+                                .clicked();
+                        let inner = egui::containers::Resize::default()
                             .id((handle.to_hex() + " resizable window").into())
                             .with_stroke(false)
                             .show(ui, |ui| {
-                                main_body(ui, handle, add_contents, forward_relations)
-                            })
+                                main_body(
+                                    ui,
+                                    handle,
+                                    options.alias,
+                                    add_contents,
+                                    forward_relations,
+                                )
+                            });
+                        (inner, show_notifications)
                     });
                 let window_center = response.rect.center().y;
                 let dot_center = Pos2::new(ui.min_rect().left(), window_center);
@@ -276,42 +484,114 @@ fn add_object(
                 Ports {
                     input: dot_center,
                     outputs,
+                    dragged: false,
+                    expand,
+                    show_notifications,
+                    inspect: false,
+                    submitted: false,
                 }
             })
             .inner
         });
 
     ctx.set_transform_layer(v.response.layer_id, clip.transform);
-    v.inner
+    Ports {
+        dragged: v.response.dragged(),
+        ..v.inner
+    }
 }
 
-fn add_fetch_buttons(ui: &mut Ui, ctx: HttpContext, handle: &Handle) {
-    if ui.button("get description").clicked() {
-        Request::Description(handle.clone()).send(ctx.clone());
-    }
+/// Shows an abbreviated handle, with the full hex on hover, which is copied
+/// when clicked.
+fn add_copyable_handle(ui: &mut Ui, handle: &Handle, text: RichText) {
+    let label = Label::new(text.monospace()).sense(Sense::click());
+    if ui
+        .add(label)
+        .on_hover_text(format!("{}\nClick to copy", handle.to_hex()))
+        .on_hover_cursor(egui::CursorIcon::Copy)
+        .clicked()
+    {
+        ui.output_mut(|o| o.copied_text = handle.to_hex())
+    };
+}
 
-    if ui.button("eval").clicked() {
-        Request::Relations(handle.clone(), Operation::Eval).send(ctx.clone());
-    }
-    if ui.button("apply").clicked() {
-        Request::Relations(handle.clone(), Operation::Apply).send(ctx.clone());
+/// Shows the metadata of a handle, and its value if it is a literal.
+fn add_kind_rows(ui: &mut Ui, handle: &Handle) {
+    ui.label("Kind:");
+    ui.label(handle.kind().to_string());
+    ui.end_row();
+    if let Some(literal) = handle.literal() {
+        ui.label("Value:");
+        ui.label(RichText::new(literal.to_string()).color(ui.style().visuals.strong_text_color()));
+        ui.end_row();
     }
+}
 
-    // // TODO: add blob, and maybe thunk pointing to tree.
-    if ui.button("get contents").clicked() {
-        // http::get_contents(ctx.clone(), handle);
-        // match handle.get_content_type() {
-        //     Object::Tree => http::get_tree_contents(ctx.clone(), handle),
-        //     Object::Tag => http::get_tag_contents(ctx.clone(), handle, None),
-        //     _ => unreachable!(),
-        // }
-        Request::Contents(handle.clone()).send(ctx.clone());
-    }
+/// Returns whether the user asked to expand the graph from the handle.
+/// The buttons are disabled while viewing a snapshot.
+fn add_fetch_buttons(
+    ui: &mut Ui,
+    ctx: HttpContext,
+    graph: &RelationStorage,
+    handle: &Handle,
+) -> bool {
+    ui.add_enabled_ui(!ctx.offline, |ui| {
+        let send = |request: Request| request.send_unless_known(ctx.clone(), graph);
+        if ui.button("get description").clicked() {
+            send(Request::Description(handle.clone()));
+        }
 
-    if ui.button("get explanations").clicked() {
-        Request::Explanations(handle.clone()).send(ctx.clone());
-    }
-    // ui.end_row();
+        if ui.button("eval").clicked() {
+            send(Request::Relations(handle.clone(), Operation::Eval));
+        }
+        if ui.button("apply").clicked() {
+            send(Request::Relations(handle.clone(), Operation::Apply));
+        }
+
+        match handle.get_content_type() {
+            // Literals already show their value.
+            _ if handle.is_literal() => {}
+            ContentType::Tree | ContentType::Thunk => {
+                if ui.button("get contents").clicked() {
+                    send(Request::Contents(handle.clone()));
+                }
+            }
+            ContentType::Tag => {
+                if ui.button("get contents").clicked() {
+                    send(Request::TagContents(handle.clone()));
+                }
+            }
+            ContentType::Blob => {
+                if ui.button("get contents").clicked() {
+                    blob::request_page(ctx.clone(), graph, handle, 0);
+                }
+            }
+        }
+
+        // These can change as the server computes more, so they can be refreshed.
+        for (name, request) in [
+            ("get explanations", Request::Explanations(handle.clone())),
+            ("get pins and tags", Request::PinsAndTags(handle.clone())),
+        ] {
+            ui.horizontal(|ui| {
+                if ui.button(name).clicked() {
+                    request.clone().send(ctx.clone());
+                }
+                if ui
+                    .small_button("⟳")
+                    .on_hover_text("Refresh, ignoring the cached response")
+                    .clicked()
+                {
+                    request.refresh(ctx.clone());
+                }
+            });
+        }
+
+        ui.button("expand")
+            .on_hover_text("Fetch contents, eval and apply relations recursively")
+            .clicked()
+    })
+    .inner
 }
 
 pub(crate) fn add_main_node(
@@ -320,13 +600,15 @@ pub(crate) fn add_main_node(
     graph: &RelationStorage,
     target_input: &mut String,
     error: &str,
+    options: NodeOptions,
     clip: TransformClip,
 ) -> Ports {
-    add_object(
+    let mut submitted = false;
+    let ports = add_object(
         &ctx.egui_ctx,
         "main object",
         handle.clone(),
-        Pos2::new(20.0, 20.0),
+        options,
         graph.forward.get(&handle),
         |ui| {
             let middle_height = Grid::new(handle.to_hex() + " properties")
@@ -334,35 +616,42 @@ pub(crate) fn add_main_node(
                 .show(ui, |ui| {
                     let start_y = ui.min_rect().bottom();
                     ui.label("Handle:");
-                    ui.text_edit_singleline(target_input);
-                    ui.end_row();
-                    ui.label("Error: ");
-                    ui.label(error);
+                    submitted = ui.text_edit_singleline(target_input).lost_focus();
                     ui.end_row();
+                    add_kind_rows(ui, &handle);
+                    if !error.is_empty() {
+                        ui.label("Error: ");
+                        ui.colored_label(ui.visuals().error_fg_color, error);
+                        ui.end_row();
+                    }
 
                     (ui.min_rect().bottom() + start_y) / 2.0
                 })
                 .inner;
 
-            add_fetch_buttons(ui, ctx.clone(), &handle);
+            let expand = add_fetch_buttons(ui, ctx.clone(), graph, &handle);
+            blob::add_blob_inspector(ui, ctx.clone(), graph, &handle);
 
-            middle_height
+            (middle_height, expand)
         },
         clip,
-    )
+    );
+    Ports { submitted, ..ports }
 }
 
 pub(crate) fn add_node(
     ctx: HttpContext,
     handle: Handle,
     graph: &RelationStorage,
+    options: NodeOptions,
     clip: TransformClip,
 ) -> Ports {
-    add_object(
+    let mut inspect = false;
+    let ports = add_object(
         &ctx.egui_ctx,
         handle.clone(),
         handle.clone(),
-        Pos2::new(20.0, 20.0),
+        options,
         graph.forward.get(&handle),
         |ui| {
             let middle_height = Grid::new(handle.to_hex() + " properties")
@@ -371,28 +660,26 @@ pub(crate) fn add_node(
                     let start_y = ui.min_rect().bottom();
                     ui.label("Handle:");
 
-                    let label = Label::new(handle.to_hex())
-                        .truncate(true)
-                        .sense(Sense::click());
-                    if ui
-                        .add(label)
-                        .on_hover_cursor(egui::CursorIcon::Copy)
-                        .clicked()
-                    {
-                        ui.output_mut(|o| o.copied_text = handle.to_hex())
-                    };
+                    add_copyable_handle(ui, &handle, RichText::new(handle.short_hex()));
                     ui.end_row();
+                    add_kind_rows(ui, &handle);
 
                     (ui.min_rect().bottom() + start_y) / 2.0
                 })
                 .inner;
 
-            add_fetch_buttons(ui, ctx.clone(), &handle);
+            inspect = ui
+                .button("inspect")
+                .on_hover_text("Make this the target")
+                .clicked();
+            let expand = add_fetch_buttons(ui, ctx.clone(), graph, &handle);
+            blob::add_blob_inspector(ui, ctx.clone(), graph, &handle);
 
-            middle_height
+            (middle_height, expand)
         },
         clip,
-    )
+    );
+    Ports { inspect, ..ports }
 }
 
 fn get_bezier(
@@ -421,17 +708,152 @@ fn get_bezier(
     )
 }
 
+/// A relation drawn between the ports of two nodes.
+pub(crate) struct Connection {
+    pub relation: Relation,
+    pub shape: CubicBezierShape,
+    pub label: Option<String>,
+}
+
+impl Connection {
+    /// The screen space distance from `pos` to the curve.
+    pub(crate) fn distance(&self, pos: Pos2) -> f32 {
+        let points = self.shape.flatten(Some(1.0));
+        points
+            .windows(2)
+            .map(|segment| {
+                let (a, b) = (segment[0], segment[1]);
+                let ab = b - a;
+                let t = ((pos - a).dot(ab) / ab.length_sq().max(f32::EPSILON)).clamp(0.0, 1.0);
+                pos.distance(a + t * ab)
+            })
+            .fold(f32::INFINITY, f32::min)
+    }
+
+    /// Whether the pointer at `pos` is on the curve.
+    pub(crate) fn hit(&self, pos: Pos2) -> bool {
+        self.distance(pos) <= self.shape.stroke.width / 2.0 + 4.0
+    }
+
+    pub(crate) fn paint(&self, painter: &egui::Painter, highlighted: bool, clip: TransformClip) {
+        // Skip curves that are entirely outside of the canvas.
+        if !self
+            .shape
+            .visual_bounding_rect()
+            .intersects(painter.clip_rect())
+        {
+            return;
+        }
+        let mut shape = self.shape;
+        if highlighted {
+            shape.stroke.width *= 1.8;
+            shape.stroke.color = shape.stroke.color.linear_multiply(1.5);
+        }
+        painter.add(shape);
+        if let Some(label) = &self.label {
+            add_connection_label(painter, &shape, label.clone(), clip);
+        }
+    }
+
+    pub(crate) fn tooltip(&self) -> String {
+        format!("{}\n{}", self.relation.lhs.to_hex(), self.relation.rhs)
+    }
+}
+
 pub(crate) fn get_connection(
+    relation: Relation,
     src: Pos2,
     dst: Pos2,
     port_type: PortType,
-    is_self_loop: bool,
+    label: Option<String>,
     clip: TransformClip,
-) -> CubicBezierShape {
+) -> Connection {
+    let is_self_loop = relation.rhs.get_port_type().map(|(_, h)| h).as_ref() == Some(&relation.lhs);
     let (src_dir, dst_dir) = if is_self_loop {
         (5.0 * (Vec2::X + Vec2::Y), -5.0 * (Vec2::X + Vec2::Y))
     } else {
         (Vec2::X, -Vec2::X)
     };
-    get_bezier(src, src_dir, dst, dst_dir, port_type.get_color(), clip)
+    Connection {
+        shape: get_bezier(src, src_dir, dst, dst_dir, port_type.get_color(), clip),
+        relation,
+        label,
+    }
+}
+
+/// Paints a label at the middle of a connection.
+fn add_connection_label(
+    painter: &egui::Painter,
+    connection: &CubicBezierShape,
+    label: String,
+    clip: TransformClip,
+) {
+    let font = egui::FontId::proportional(14.0 * clip.transform.scaling);
+    let color = painter.ctx().style().visuals.strong_text_color();
+    let galley = painter.layout_no_wrap(label, font, color);
+    let rect = egui::Align2::CENTER_BOTTOM
+        .anchor_rect(Rect::from_min_size(connection.sample(0.5), galley.size()));
+    painter.rect_filled(
+        rect.expand(2.0),
+        2.0,
+        painter.ctx().style().visuals.panel_fill,
+    );
+    painter.galley(rect.min, galley, color);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::handle::test_handle;
+
+    #[test]
+    fn finds_paths_both_ways() {
+        let mut graph = RelationStorage::default();
+        let tree = Relation::new(test_handle(1), RelationRhs::TreeEntry(test_handle(2), 0));
+        let eval = Relation::new(test_handle(2), RelationRhs::Eval(test_handle(3)));
+        let parent = Relation::new(test_handle(4), RelationRhs::Apply(test_handle(1)));
+        for relation in [&tree, &eval, &parent] {
+            graph.insert(relation.clone());
+        }
+        assert_eq!(
+            graph.path(&test_handle(1), &test_handle(3)),
+            Some(vec![tree.clone(), eval.clone()])
+        );
+        assert_eq!(
+            graph.path(&test_handle(3), &test_handle(4)),
+            Some(vec![eval, tree, parent])
+        );
+        assert_eq!(graph.path(&test_handle(1), &test_handle(1)), Some(vec![]));
+        assert_eq!(graph.path(&test_handle(1), &test_handle(5)), None);
+    }
+
+    #[test]
+    fn resolves_prefixes() {
+        let mut graph = RelationStorage::default();
+        let mut similar = test_handle(0xab);
+        similar.content[2] = 0xcd;
+        graph.insert(Relation::new(
+            test_handle(0xab),
+            RelationRhs::Eval(similar.clone()),
+        ));
+        graph.insert(Relation::new(
+            test_handle(0x12),
+            RelationRhs::Eval(test_handle(0xab)),
+        ));
+        assert_eq!(graph.resolve("1212").unwrap(), test_handle(0x12));
+        assert_eq!(graph.resolve("ABABA").unwrap(), test_handle(0xab));
+        assert_eq!(graph.resolve(" ababc ").unwrap(), similar);
+        let error = graph.resolve("abab").unwrap_err().to_string();
+        assert!(error.contains(&similar.to_hex()) && error.contains(&test_handle(0xab).to_hex()));
+        assert!(graph.resolve("3434").is_err());
+        // Too short, even though only one handle starts with 1.
+        assert!(graph.resolve("121").is_err());
+        assert!(graph.resolve("xyxy").is_err());
+        assert!(graph.resolve(&format!("aé{}", "0".repeat(61))).is_err());
+        // Full handles do not have to be known.
+        assert_eq!(
+            graph.resolve(&test_handle(0x34).to_hex()).unwrap(),
+            test_handle(0x34)
+        );
+    }
 }
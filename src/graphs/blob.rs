@@ -0,0 +1,220 @@
+use std::fmt::Write;
+
+use egui::{ComboBox, RichText, Ui};
+
+use crate::{
+    handle::Handle,
+    http::{HttpContext, Request},
+};
+
+use super::{RelationRhs, RelationStorage};
+
+/// The number of bytes fetched at a time, so that large blobs are not loaded
+/// into a node all at once.
+pub(crate) const PAGE_SIZE: u64 = 256;
+
+#[derive(Clone, Copy, PartialEq, Default)]
+enum BlobView {
+    #[default]
+    Hex,
+    Text,
+    Integers {
+        width: usize,
+        big_endian: bool,
+    },
+}
+
+impl BlobView {
+    const ALL: [BlobView; 9] = [
+        BlobView::Hex,
+        BlobView::Text,
+        BlobView::Integers {
+            width: 1,
+            big_endian: false,
+        },
+        BlobView::Integers {
+            width: 2,
+            big_endian: false,
+        },
+        BlobView::Integers {
+            width: 2,
+            big_endian: true,
+        },
+        BlobView::Integers {
+            width: 4,
+            big_endian: false,
+        },
+        BlobView::Integers {
+            width: 4,
+            big_endian: true,
+        },
+        BlobView::Integers {
+            width: 8,
+            big_endian: false,
+        },
+        BlobView::Integers {
+            width: 8,
+            big_endian: true,
+        },
+    ];
+
+    fn name(&self) -> String {
+        match self {
+            BlobView::Hex => "hex".to_owned(),
+            BlobView::Text => "utf-8".to_owned(),
+            BlobView::Integers { width: 1, .. } => "u8".to_owned(),
+            BlobView::Integers { width, big_endian } => {
+                format!("u{} {}", width * 8, if *big_endian { "be" } else { "le" })
+            }
+        }
+    }
+
+    fn render(&self, offset: u64, data: &[u8]) -> String {
+        let mut s = String::new();
+        match self {
+            BlobView::Hex => {
+                for (i, line) in data.chunks(16).enumerate() {
+                    let _ = write!(s, "{:08x} ", offset + 16 * i as u64);
+                    for byte in line {
+                        let _ = write!(s, " {byte:02x}");
+                    }
+                    s.push_str(&"   ".repeat(16 - line.len()));
+                    s.push_str("  |");
+                    s.extend(line.iter().map(|b| {
+                        if b.is_ascii_graphic() || *b == b' ' {
+                            *b as char
+                        } else {
+                            '.'
+                        }
+                    }));
+                    s.push_str("|\n");
+                }
+            }
+            BlobView::Text => s = String::from_utf8_lossy(data).into_owned(),
+            BlobView::Integers { width, big_endian } => {
+                for (i, int) in data.chunks(*width).enumerate() {
+                    // A trailing partial integer is padded with zeroes.
+                    let mut bytes = [0_u8; 8];
+                    let value = if *big_endian {
+                        bytes[8 - width..][..int.len()].copy_from_slice(int);
+                        u64::from_be_bytes(bytes)
+                    } else {
+                        bytes[..int.len()].copy_from_slice(int);
+                        u64::from_le_bytes(bytes)
+                    };
+                    let _ = writeln!(s, "[{}] {}", offset / *width as u64 + i as u64, value);
+                }
+            }
+        }
+        s
+    }
+}
+
+#[derive(Clone, Copy, Default)]
+struct InspectorState {
+    page: u64,
+    view: BlobView,
+}
+
+/// Requests one page of a blob, unless it was fetched before.
+pub(crate) fn request_page(ctx: HttpContext, graph: &RelationStorage, handle: &Handle, page: u64) {
+    Request::BlobContents(handle.clone(), page * PAGE_SIZE, PAGE_SIZE)
+        .send_unless_known(ctx, graph);
+}
+
+/// Shows the fetched bytes of a blob one page at a time.
+pub(crate) fn add_blob_inspector(
+    ui: &mut Ui,
+    ctx: HttpContext,
+    graph: &RelationStorage,
+    handle: &Handle,
+) {
+    let chunks: Vec<_> = graph
+        .forward
+        .get(handle)
+        .into_iter()
+        .flatten()
+        .filter_map(|r| match &r.rhs {
+            RelationRhs::BlobChunk(offset, data) => Some((*offset, data)),
+            _ => None,
+        })
+        .collect();
+    if chunks.is_empty() {
+        return;
+    }
+
+    let id = ui.make_persistent_id(handle.to_hex() + " blob inspector");
+    let mut state: InspectorState = ui.data_mut(|d| *d.get_temp_mut_or_default(id));
+    let pages = ((handle.kind().size() + PAGE_SIZE - 1) / PAGE_SIZE).max(1);
+
+    ui.separator();
+    ui.horizontal(|ui| {
+        ComboBox::from_id_source(id.with("view"))
+            .selected_text(state.view.name())
+            .show_ui(ui, |ui| {
+                for view in BlobView::ALL {
+                    ui.selectable_value(&mut state.view, view, view.name());
+                }
+            });
+        let previous = ui.add_enabled(state.page > 0, egui::Button::new("<"));
+        ui.label(format!("page {}/{}", state.page + 1, pages));
+        let next = ui.add_enabled(state.page + 1 < pages, egui::Button::new(">"));
+        if previous.clicked() {
+            state.page -= 1;
+        }
+        if next.clicked() {
+            state.page += 1;
+        }
+        // Pages are only fetched once they are viewed.
+        if (previous.clicked() || next.clicked())
+            && !chunks.iter().any(|(o, _)| *o == state.page * PAGE_SIZE)
+        {
+            request_page(ctx.clone(), graph, handle, state.page);
+        }
+    });
+
+    match chunks.iter().find(|(o, _)| *o == state.page * PAGE_SIZE) {
+        Some((offset, data)) => {
+            egui::ScrollArea::vertical()
+                .id_source(id.with("scroll"))
+                .max_height(200.0)
+                .show(ui, |ui| {
+                    ui.label(RichText::new(state.view.render(*offset, data)).monospace());
+                });
+        }
+        None => {
+            if ui.button("load page").clicked() {
+                request_page(ctx, graph, handle, state.page);
+            }
+        }
+    }
+
+    ui.data_mut(|d| d.insert_temp(id, state));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_integers() {
+        let data = [1, 0, 0, 2, 3];
+        let le = BlobView::Integers {
+            width: 2,
+            big_endian: false,
+        };
+        assert_eq!(le.render(4, &data), "[2] 1\n[3] 512\n[4] 3\n");
+        let be = BlobView::Integers {
+            width: 2,
+            big_endian: true,
+        };
+        assert_eq!(be.render(4, &data), "[2] 256\n[3] 2\n[4] 768\n");
+    }
+
+    #[test]
+    fn renders_hex_dump() {
+        let dump = BlobView::Hex.render(256, b"fix\n");
+        assert!(dump.starts_with("00000100  66 69 78 0a "));
+        assert!(dump.ends_with("|fix.|\n"));
+    }
+}
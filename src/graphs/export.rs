@@ -0,0 +1,489 @@
+//! Exports the relation graph for use outside of the viewer.
+//!
+//! The JSON format is:
+//!
+//! ```json
+//! {
+//!   "version": 1,
+//!   "root": "<64 hex characters, or null for the whole graph>",
+//!   "nodes": [
+//!     {
+//!       "handle": "<64 hex characters>",
+//!       "kind": "tree object (3 entries)",
+//!       "value": "uint32:9 (only for literals)",
+//!       "description": "<description, if fetched>"
+//!     }
+//!   ],
+//!   "edges": [
+//!     {
+//!       "from": "<64 hex characters>",
+//!       "to": "<64 hex characters>",
+//!       "relation": "eval | apply | pin | tag_author | tag_target | tag_label | tree_entry",
+//!       "index": 0,
+//!       "label": "has entry at index [0]"
+//!     }
+//!   ]
+//! }
+//! ```
+//!
+//! `index` is only present for `tree_entry` edges. Blob contents are not
+//! exported. Only `handle`, `description` and the edges are read back by
+//! [`import`].
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Write,
+};
+
+use anyhow::{ensure, Context, Result};
+use egui::{ComboBox, Ui};
+
+use crate::{files, handle::Handle};
+
+use super::{Relation, RelationRhs, RelationStorage};
+
+const VERSION: u32 = 1;
+
+#[derive(Clone, Copy, PartialEq)]
+pub(crate) enum Format {
+    Dot,
+    Mermaid,
+    Json,
+}
+
+impl Format {
+    fn name(&self) -> &'static str {
+        match self {
+            Format::Dot => "Graphviz DOT",
+            Format::Mermaid => "Mermaid",
+            Format::Json => "JSON",
+        }
+    }
+
+    fn extension(&self) -> &'static str {
+        match self {
+            Format::Dot => "dot",
+            Format::Mermaid => "mmd",
+            Format::Json => "json",
+        }
+    }
+}
+
+#[derive(serde::Deserialize, serde::Serialize)]
+pub(crate) struct JsonGraph {
+    pub version: u32,
+    pub root: Option<String>,
+    pub nodes: Vec<JsonNode>,
+    pub edges: Vec<JsonEdge>,
+}
+
+#[derive(serde::Deserialize, serde::Serialize)]
+pub(crate) struct JsonNode {
+    pub handle: String,
+    pub kind: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub value: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+}
+
+#[derive(serde::Deserialize, serde::Serialize)]
+pub(crate) struct JsonEdge {
+    pub from: String,
+    pub to: String,
+    pub relation: JsonRelation,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub index: Option<usize>,
+    pub label: String,
+}
+
+#[derive(serde::Deserialize, serde::Serialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum JsonRelation {
+    Eval,
+    Apply,
+    Pin,
+    TagAuthor,
+    TagTarget,
+    TagLabel,
+    TreeEntry,
+}
+
+/// The part of the graph being exported.
+struct Subgraph<'a> {
+    root: Option<&'a Handle>,
+    /// Handles with their descriptions.
+    nodes: BTreeMap<&'a Handle, Option<&'a str>>,
+    edges: Vec<(&'a Relation, &'a Handle)>,
+}
+
+impl<'a> Subgraph<'a> {
+    /// Everything, or only what is reachable from `root`.
+    fn new(graph: &'a RelationStorage, root: Option<&'a Handle>) -> Self {
+        let mut relations: BTreeSet<&Relation> = BTreeSet::new();
+        match root {
+            Some(root) => graph.visit_bfs(root.clone(), |r| {
+                relations.insert(r);
+            }),
+            None => relations.extend(graph.forward.values().flatten()),
+        }
+
+        let mut nodes = BTreeMap::new();
+        if let Some(root) = root {
+            nodes.insert(root, None);
+        }
+        let mut edges = vec![];
+        for relation in relations {
+            if let Some(rhs) = edge_target(&relation.rhs) {
+                nodes.entry(&relation.lhs).or_insert(None);
+                nodes.entry(rhs).or_insert(None);
+                edges.push((relation, rhs));
+            }
+        }
+        for (handle, description) in &mut nodes {
+            *description = graph.forward.get(*handle).and_then(|relations| {
+                relations.iter().find_map(|r| match &r.rhs {
+                    RelationRhs::Description(d) => Some(d.as_str()),
+                    _ => None,
+                })
+            });
+        }
+        Self { root, nodes, edges }
+    }
+
+    fn node_label(handle: &Handle, description: Option<&str>) -> String {
+        let mut label = handle.to_hex();
+        let _ = write!(label, "\n{}", handle.kind());
+        if let Some(literal) = handle.literal() {
+            let _ = write!(label, "\n{literal}");
+        }
+        if let Some(description) = description {
+            let _ = write!(label, "\n{description}");
+        }
+        label
+    }
+
+    fn to_dot(&self) -> String {
+        fn escape(s: &str) -> String {
+            s.replace('\\', "\\\\")
+                .replace('"', "\\\"")
+                .replace('\n', "\\n")
+        }
+        let mut s =
+            "digraph fix {\n  rankdir=LR;\n  node [shape=box, fontname=monospace];\n".to_owned();
+        for (handle, description) in &self.nodes {
+            let _ = writeln!(
+                s,
+                "  \"{}\" [label=\"{}\"];",
+                handle.to_hex(),
+                escape(&Self::node_label(handle, *description))
+            );
+        }
+        for (relation, rhs) in &self.edges {
+            let _ = writeln!(
+                s,
+                "  \"{}\" -> \"{}\" [label=\"{}\"];",
+                relation.lhs.to_hex(),
+                rhs.to_hex(),
+                escape(&relation.rhs.get_abbrev())
+            );
+        }
+        s.push_str("}\n");
+        s
+    }
+
+    fn to_mermaid(&self) -> String {
+        fn escape(s: &str) -> String {
+            s.replace('"', "#quot;").replace('\n', "<br>")
+        }
+        let ids: BTreeMap<&Handle, usize> = self
+            .nodes
+            .keys()
+            .enumerate()
+            .map(|(i, h)| (*h, i))
+            .collect();
+        let mut s = "flowchart LR\n".to_owned();
+        for (handle, description) in &self.nodes {
+            let _ = writeln!(
+                s,
+                "  n{}[\"{}\"]",
+                ids[handle],
+                escape(&Self::node_label(handle, *description))
+            );
+        }
+        for (relation, rhs) in &self.edges {
+            let _ = writeln!(
+                s,
+                "  n{} -->|\"{}\"| n{}",
+                ids[&relation.lhs],
+                escape(&relation.rhs.get_abbrev()),
+                ids[rhs]
+            );
+        }
+        s
+    }
+
+    fn to_json(&self) -> Result<String> {
+        let graph = JsonGraph {
+            version: VERSION,
+            root: self.root.map(Handle::to_hex),
+            nodes: self
+                .nodes
+                .iter()
+                .map(|(handle, description)| JsonNode {
+                    handle: handle.to_hex(),
+                    kind: handle.kind().to_string(),
+                    value: handle.literal().map(|l| l.to_string()),
+                    description: description.map(str::to_owned),
+                })
+                .collect(),
+            edges: self
+                .edges
+                .iter()
+                .filter_map(|(relation, rhs)| {
+                    let (kind, index) = json_relation(&relation.rhs)?;
+                    Some(JsonEdge {
+                        from: relation.lhs.to_hex(),
+                        to: rhs.to_hex(),
+                        relation: kind,
+                        index,
+                        label: relation.rhs.get_abbrev().into_owned(),
+                    })
+                })
+                .collect(),
+        };
+        serde_json::to_string_pretty(&graph).context("serializing graph")
+    }
+}
+
+fn edge_target(rhs: &RelationRhs) -> Option<&Handle> {
+    match rhs {
+        RelationRhs::Eval(h)
+        | RelationRhs::Apply(h)
+        | RelationRhs::Pin(h)
+        | RelationRhs::TagAuthor(h)
+        | RelationRhs::TagTarget(h)
+        | RelationRhs::TagLabel(h)
+        | RelationRhs::TreeEntry(h, _) => Some(h),
+        RelationRhs::Description(_) | RelationRhs::BlobChunk(..) => None,
+    }
+}
+
+fn json_relation(rhs: &RelationRhs) -> Option<(JsonRelation, Option<usize>)> {
+    Some(match rhs {
+        RelationRhs::Eval(_) => (JsonRelation::Eval, None),
+        RelationRhs::Apply(_) => (JsonRelation::Apply, None),
+        RelationRhs::Pin(_) => (JsonRelation::Pin, None),
+        RelationRhs::TagAuthor(_) => (JsonRelation::TagAuthor, None),
+        RelationRhs::TagTarget(_) => (JsonRelation::TagTarget, None),
+        RelationRhs::TagLabel(_) => (JsonRelation::TagLabel, None),
+        RelationRhs::TreeEntry(_, i) => (JsonRelation::TreeEntry, Some(*i)),
+        RelationRhs::Description(_) | RelationRhs::BlobChunk(..) => return None,
+    })
+}
+
+/// Writes a relation as one line of JSON. Relations between handles are
+/// written like edges of the exported graph, while descriptions and blob
+/// chunks are written with the handle they are about.
+pub(crate) fn json_line(relation: &Relation) -> String {
+    let from = relation.lhs.to_hex();
+    let value = match &relation.rhs {
+        RelationRhs::Description(description) => {
+            serde_json::json!({ "handle": from, "description": description })
+        }
+        RelationRhs::BlobChunk(offset, data) => {
+            serde_json::json!({ "handle": from, "offset": offset, "data": data })
+        }
+        rhs => match edge_target(rhs).zip(json_relation(rhs)) {
+            Some((to, (relation, index))) => serde_json::to_value(JsonEdge {
+                from,
+                to: to.to_hex(),
+                relation,
+                index,
+                label: rhs.get_abbrev().into_owned(),
+            })
+            .unwrap_or_default(),
+            None => serde_json::Value::Null,
+        },
+    };
+    value.to_string()
+}
+
+/// Exports the graph, or only the part reachable from `root`.
+pub(crate) fn export(
+    graph: &RelationStorage,
+    root: Option<&Handle>,
+    format: Format,
+) -> Result<String> {
+    let subgraph = Subgraph::new(graph, root);
+    match format {
+        Format::Dot => Ok(subgraph.to_dot()),
+        Format::Mermaid => Ok(subgraph.to_mermaid()),
+        Format::Json => subgraph.to_json(),
+    }
+}
+
+/// Reads a graph exported as JSON, along with the root it was exported from.
+pub(crate) fn import(json: &str) -> Result<(RelationStorage, Option<Handle>)> {
+    let parse = |h: &str| Handle::from_hex(h).with_context(|| format!("parsing {h}"));
+    let json: JsonGraph = serde_json::from_str(json).context("parsing graph json")?;
+    ensure!(
+        json.version <= VERSION,
+        "graph version {} is newer than the supported version {}",
+        json.version,
+        VERSION
+    );
+
+    let mut graph = RelationStorage::default();
+    for node in json.nodes {
+        if let Some(description) = node.description {
+            graph.insert(Relation::new(
+                parse(&node.handle)?,
+                RelationRhs::Description(description),
+            ));
+        }
+    }
+    for edge in json.edges {
+        let to = parse(&edge.to)?;
+        let rhs = match edge.relation {
+            JsonRelation::Eval => RelationRhs::Eval(to),
+            JsonRelation::Apply => RelationRhs::Apply(to),
+            JsonRelation::Pin => RelationRhs::Pin(to),
+            JsonRelation::TagAuthor => RelationRhs::TagAuthor(to),
+            JsonRelation::TagTarget => RelationRhs::TagTarget(to),
+            JsonRelation::TagLabel => RelationRhs::TagLabel(to),
+            JsonRelation::TreeEntry => RelationRhs::TreeEntry(
+                to,
+                edge.index.context("expected tree entry to have an index")?,
+            ),
+        };
+        graph.insert(Relation::new(parse(&edge.from)?, rhs));
+    }
+    Ok((graph, json.root.as_deref().map(parse).transpose()?))
+}
+
+/// Controls for exporting the graph to a file.
+pub(crate) struct ExportPanel {
+    format: Format,
+    reachable_only: bool,
+    file_name: String,
+    status: String,
+}
+
+impl Default for ExportPanel {
+    fn default() -> Self {
+        Self {
+            format: Format::Dot,
+            reachable_only: true,
+            file_name: "fix-graph".to_owned(),
+            status: String::new(),
+        }
+    }
+}
+
+impl ExportPanel {
+    pub(crate) fn ui(&mut self, ui: &mut Ui, graph: &RelationStorage, target: &Handle) {
+        ui.heading("Export");
+        ComboBox::from_id_source("export format")
+            .selected_text(self.format.name())
+            .show_ui(ui, |ui| {
+                for format in [Format::Dot, Format::Mermaid, Format::Json] {
+                    ui.selectable_value(&mut self.format, format, format.name());
+                }
+            });
+        ui.checkbox(&mut self.reachable_only, "only reachable from target");
+        ui.horizontal(|ui| {
+            ui.label("File:");
+            ui.text_edit_singleline(&mut self.file_name);
+        });
+        let action = if cfg!(target_arch = "wasm32") {
+            "download"
+        } else {
+            "save"
+        };
+        if ui.button(action).clicked() {
+            let name = format!("{}.{}", self.file_name, self.format.extension());
+            let result = export(graph, self.reachable_only.then_some(target), self.format)
+                .and_then(|contents| files::save(&name, &contents));
+            self.status = match result {
+                Ok(()) => format!("saved {name}"),
+                Err(e) => format!("{e:#}"),
+            };
+        }
+        if !self.status.is_empty() {
+            ui.small(&self.status);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::handle::test_handle;
+
+    fn graph() -> RelationStorage {
+        let mut graph = RelationStorage::default();
+        graph.insert(Relation::new(
+            test_handle(0),
+            RelationRhs::TreeEntry(test_handle(1), 0),
+        ));
+        graph.insert(Relation::new(
+            test_handle(1),
+            RelationRhs::Eval(test_handle(2)),
+        ));
+        graph.insert(Relation::new(
+            test_handle(1),
+            RelationRhs::Description("say \"hi\"".to_owned()),
+        ));
+        graph.insert(Relation::new(
+            test_handle(3),
+            RelationRhs::Apply(test_handle(4)),
+        ));
+        graph
+    }
+
+    #[test]
+    fn exports_reachable_part() {
+        let graph = graph();
+        let dot = export(&graph, Some(&test_handle(0)), Format::Dot).unwrap();
+        assert_eq!(dot.matches(" -> ").count(), 2);
+        assert!(dot.contains(r#"say \"hi\""#));
+        assert!(!dot.contains(&test_handle(4).to_hex()));
+
+        let dot = export(&graph, None, Format::Dot).unwrap();
+        assert_eq!(dot.matches(" -> ").count(), 3);
+    }
+
+    #[test]
+    fn exports_mermaid() {
+        let mermaid = export(&graph(), None, Format::Mermaid).unwrap();
+        assert!(mermaid.starts_with("flowchart LR\n"));
+        assert!(mermaid.contains("  n0 -->|\"has entry at index [0]\"| n1\n"));
+        assert!(mermaid.contains("say #quot;hi#quot;"));
+    }
+
+    #[test]
+    fn exports_json() {
+        let json = export(&graph(), Some(&test_handle(0)), Format::Json).unwrap();
+        let parsed: JsonGraph = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed.root, Some(test_handle(0).to_hex()));
+        assert_eq!(parsed.nodes.len(), 3);
+        assert_eq!(parsed.nodes[1].description.as_deref(), Some("say \"hi\""));
+        assert_eq!(parsed.edges[0].relation, JsonRelation::TreeEntry);
+        assert_eq!(parsed.edges[0].index, Some(0));
+        assert_eq!(parsed.edges[1].relation, JsonRelation::Eval);
+    }
+
+    #[test]
+    fn imports_exported_json() {
+        let graph = graph();
+        let json = export(&graph, None, Format::Json).unwrap();
+        let (imported, root) = import(&json).unwrap();
+        assert_eq!(root, None);
+        assert_eq!(imported.forward, graph.forward);
+        assert_eq!(imported.backward, graph.backward);
+
+        let newer = json.replacen("\"version\": 1", "\"version\": 2", 1);
+        assert!(import(&newer).is_err());
+    }
+}
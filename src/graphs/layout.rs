@@ -0,0 +1,200 @@
+use std::collections::HashMap;
+
+use egui::Pos2;
+
+use crate::handle::Handle;
+
+use super::RelationStorage;
+
+const ORIGIN: Pos2 = Pos2::new(20.0, 20.0);
+const LAYER_SPACING: f32 = 620.0;
+const NODE_SPACING: f32 = 240.0;
+const SWEEPS: usize = 4;
+
+/// Places the graph reachable from `root` into layers by their distance from
+/// it, so that relations point from left to right.
+/// Within each layer, nodes are ordered to reduce crossings using the
+/// barycenter heuristic.
+pub(crate) fn layered_layout(graph: &RelationStorage, root: &Handle) -> HashMap<Handle, Pos2> {
+    let mut layer_of: HashMap<Handle, i32> = HashMap::from([(root.clone(), 0)]);
+    let mut discovered = vec![root.clone()];
+    graph.visit_bfs(root.clone(), |relation| {
+        let Some((_, rhs)) = relation.rhs.get_port_type() else {
+            return;
+        };
+        // One end of each visited relation has already been placed.
+        let (new, layer) = match (layer_of.get(&relation.lhs), layer_of.get(&rhs)) {
+            (Some(l), None) => (rhs.clone(), l + 1),
+            (None, Some(l)) => (relation.lhs.clone(), l - 1),
+            _ => return,
+        };
+        layer_of.insert(new.clone(), layer);
+        discovered.push(new);
+    });
+    // The search only visits relations to nodes it hasn't seen yet, so the
+    // relations between placed nodes are collected separately.
+    let edges: Vec<(&Handle, Handle)> = discovered
+        .iter()
+        .filter_map(|handle| graph.forward.get(handle))
+        .flatten()
+        .filter_map(|relation| {
+            let (_, rhs) = relation.rhs.get_port_type()?;
+            layer_of.contains_key(&rhs).then_some((&relation.lhs, rhs))
+        })
+        .collect();
+
+    let min_layer = layer_of.values().copied().min().unwrap_or(0);
+    let max_layer = layer_of.values().copied().max().unwrap_or(0);
+    let mut layers: Vec<Vec<Handle>> = vec![vec![]; (max_layer - min_layer + 1) as usize];
+    for handle in discovered {
+        layers[(layer_of[&handle] - min_layer) as usize].push(handle);
+    }
+
+    let mut neighbours: HashMap<&Handle, Vec<&Handle>> = HashMap::new();
+    for (lhs, rhs) in &edges {
+        neighbours.entry(lhs).or_default().push(rhs);
+        neighbours.entry(rhs).or_default().push(*lhs);
+    }
+
+    fn update_indices(layers: &[Vec<Handle>], index_of: &mut HashMap<Handle, f32>) {
+        for layer in layers {
+            for (i, handle) in layer.iter().enumerate() {
+                index_of.insert(handle.clone(), i as f32);
+            }
+        }
+    }
+    let mut index_of = HashMap::new();
+    update_indices(&layers, &mut index_of);
+
+    for sweep in 0..SWEEPS {
+        let order: Vec<usize> = if sweep % 2 == 0 {
+            (1..layers.len()).collect()
+        } else {
+            (0..layers.len().saturating_sub(1)).rev().collect()
+        };
+        let fixed_offset: isize = if sweep % 2 == 0 { -1 } else { 1 };
+        for i in order {
+            let fixed = (i as isize + fixed_offset) as usize;
+            let barycenter = |handle: &Handle| {
+                let positions: Vec<f32> = neighbours
+                    .get(handle)
+                    .into_iter()
+                    .flatten()
+                    .filter(|n| layer_of[**n] - min_layer == fixed as i32)
+                    .map(|n| index_of[*n])
+                    .collect();
+                if positions.is_empty() {
+                    // Keep nodes without neighbours in the fixed layer in place.
+                    index_of[handle]
+                } else {
+                    positions.iter().sum::<f32>() / positions.len() as f32
+                }
+            };
+            let mut keyed: Vec<(f32, Handle)> =
+                layers[i].drain(..).map(|h| (barycenter(&h), h)).collect();
+            keyed.sort_by(|a, b| a.0.total_cmp(&b.0));
+            layers[i] = keyed.into_iter().map(|(_, h)| h).collect();
+            update_indices(&layers, &mut index_of);
+        }
+    }
+
+    layers
+        .into_iter()
+        .enumerate()
+        .flat_map(|(x, layer)| {
+            layer.into_iter().enumerate().map(move |(y, handle)| {
+                (
+                    handle,
+                    ORIGIN + egui::vec2(x as f32 * LAYER_SPACING, y as f32 * NODE_SPACING),
+                )
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        graphs::{Relation, RelationRhs},
+        handle::test_handle,
+    };
+
+    #[test]
+    fn layers_by_distance_from_root() {
+        let mut graph = RelationStorage::default();
+        graph.insert(Relation::new(
+            test_handle(0),
+            RelationRhs::TreeEntry(test_handle(1), 0),
+        ));
+        graph.insert(Relation::new(
+            test_handle(0),
+            RelationRhs::TreeEntry(test_handle(2), 1),
+        ));
+        graph.insert(Relation::new(
+            test_handle(1),
+            RelationRhs::Eval(test_handle(3)),
+        ));
+        graph.insert(Relation::new(
+            test_handle(4),
+            RelationRhs::Apply(test_handle(0)),
+        ));
+
+        let layout = layered_layout(&graph, &test_handle(0));
+        let x = |i| layout[&test_handle(i)].x;
+        assert_eq!(layout.len(), 5);
+        assert!(x(4) < x(0));
+        assert!(x(0) < x(1));
+        assert_eq!(x(1), x(2));
+        assert!(x(1) < x(3));
+        assert_eq!(x(4), ORIGIN.x);
+    }
+
+    #[test]
+    fn orders_children_by_parents() {
+        let mut graph = RelationStorage::default();
+        graph.insert(Relation::new(
+            test_handle(0),
+            RelationRhs::TreeEntry(test_handle(1), 0),
+        ));
+        graph.insert(Relation::new(
+            test_handle(0),
+            RelationRhs::TreeEntry(test_handle(2), 1),
+        ));
+        // Children of the lower node should not cross over the upper node's.
+        graph.insert(Relation::new(
+            test_handle(2),
+            RelationRhs::Eval(test_handle(3)),
+        ));
+        graph.insert(Relation::new(
+            test_handle(1),
+            RelationRhs::Eval(test_handle(4)),
+        ));
+
+        let layout = layered_layout(&graph, &test_handle(0));
+        let y = |i| layout[&test_handle(i)].y;
+        assert_eq!(y(1) < y(2), y(4) < y(3));
+    }
+
+    #[test]
+    fn orders_by_relations_between_placed_nodes() {
+        let mut graph = RelationStorage::default();
+        for (lhs, rhs, index) in [(0, 1, 0), (0, 2, 1), (1, 3, 0), (1, 4, 1), (2, 5, 0)] {
+            graph.insert(Relation::new(
+                test_handle(lhs),
+                RelationRhs::TreeEntry(test_handle(rhs), index),
+            ));
+        }
+        // 3 is already placed when the search reaches 2, but this still pulls
+        // it towards 2.
+        graph.insert(Relation::new(
+            test_handle(2),
+            RelationRhs::Eval(test_handle(3)),
+        ));
+
+        let layout = layered_layout(&graph, &test_handle(0));
+        let y = |i| layout[&test_handle(i)].y;
+        assert!(y(4) < y(3));
+        assert!(y(3) < y(5));
+    }
+}
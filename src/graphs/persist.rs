@@ -0,0 +1,143 @@
+use std::collections::HashSet;
+
+use crate::handle::Handle;
+
+use super::{Relation, RelationRhs, RelationStorage};
+
+/// The key the graph is stored under, next to [`eframe::APP_KEY`].
+/// On the web, eframe keeps its storage in `localStorage`.
+pub(crate) const RELATIONS_KEY: &str = "relations";
+
+/// Bumped when the meaning of stored relations changes, which discards them.
+const VERSION: u32 = 1;
+
+/// Stays well below the `localStorage` quota of a few megabytes, which is
+/// shared with the rest of the app's state.
+const MAX_BYTES: usize = 1 << 20;
+
+/// The relation graph as saved between sessions.
+///
+/// Each relation is stored as its own JSON string, so that relations which no
+/// longer deserialize after a format change are skipped instead of
+/// discarding the whole graph.
+#[derive(serde::Deserialize, serde::Serialize, Default)]
+#[serde(default)]
+pub(crate) struct PersistedGraph {
+    version: u32,
+    relations: Vec<String>,
+}
+
+impl RelationStorage {
+    /// Serializes the graph, keeping what is reachable from `root` if it does
+    /// not all fit. Blob contents are dropped first since they can be large
+    /// and are cheap to fetch again.
+    pub(crate) fn to_persisted(&self, root: &Handle) -> PersistedGraph {
+        let mut ordered: Vec<&Relation> = vec![];
+        self.visit_bfs(root.clone(), |r| ordered.push(r));
+        let mut reachable: HashSet<Handle> = HashSet::from([root.clone()]);
+        for relation in &ordered {
+            reachable.insert(relation.lhs.clone());
+            reachable.extend(relation.rhs.get_port_type().map(|(_, h)| h));
+        }
+        let visited: HashSet<&Relation> = ordered.iter().copied().collect();
+        let (near, far): (Vec<&Relation>, Vec<&Relation>) = self
+            .forward
+            .values()
+            .flatten()
+            .filter(|r| !visited.contains(r))
+            .partition(|r| reachable.contains(&r.lhs));
+        ordered.extend(near);
+        ordered.extend(far);
+        // The sort is stable, so the order is otherwise kept.
+        ordered.sort_by_key(|r| matches!(r.rhs, RelationRhs::BlobChunk(..)));
+
+        let mut bytes = 0;
+        let relations = ordered
+            .into_iter()
+            .filter_map(|r| serde_json::to_string(r).ok())
+            .take_while(|s| {
+                bytes += s.len();
+                bytes <= MAX_BYTES
+            })
+            .collect();
+        PersistedGraph {
+            version: VERSION,
+            relations,
+        }
+    }
+
+    pub(crate) fn from_persisted(persisted: PersistedGraph) -> Self {
+        let mut storage = Self::default();
+        if persisted.version != VERSION {
+            return storage;
+        }
+        for relation in persisted.relations {
+            if let Ok(relation) = serde_json::from_str::<Relation>(&relation) {
+                storage.insert(relation);
+            }
+        }
+        storage
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::handle::test_handle;
+
+    #[test]
+    fn round_trips() {
+        let mut graph = RelationStorage::default();
+        let relations = [
+            Relation::new(test_handle(0), RelationRhs::TreeEntry(test_handle(1), 0)),
+            Relation::new(test_handle(1), RelationRhs::Description("add".to_owned())),
+            Relation::new(test_handle(2), RelationRhs::BlobChunk(0, vec![1, 2, 3])),
+        ];
+        for relation in relations.clone() {
+            graph.insert(relation);
+        }
+        let loaded = RelationStorage::from_persisted(graph.to_persisted(&test_handle(0)));
+        for relation in relations {
+            assert!(loaded.forward[&relation.lhs].contains(&relation));
+        }
+    }
+
+    #[test]
+    fn skips_unknown_relations() {
+        let mut graph = RelationStorage::default();
+        graph.insert(Relation::new(
+            test_handle(0),
+            RelationRhs::Eval(test_handle(1)),
+        ));
+        let mut persisted = graph.to_persisted(&test_handle(0));
+        persisted
+            .relations
+            .push(r#"{"lhs":"removed","rhs":{"Renamed":[]}}"#.to_owned());
+        let loaded = RelationStorage::from_persisted(persisted);
+        assert_eq!(loaded.forward.len(), 1);
+
+        let mut persisted = graph.to_persisted(&test_handle(0));
+        persisted.version += 1;
+        assert!(RelationStorage::from_persisted(persisted)
+            .forward
+            .is_empty());
+    }
+
+    #[test]
+    fn drops_blobs_first() {
+        let mut graph = RelationStorage::default();
+        let page = vec![0; 1024];
+        for i in 0..(MAX_BYTES / page.len()) as u64 {
+            graph.insert(Relation::new(
+                test_handle(1),
+                RelationRhs::BlobChunk(i, page.clone()),
+            ));
+        }
+        let kept = Relation::new(test_handle(0), RelationRhs::Eval(test_handle(1)));
+        graph.insert(kept.clone());
+        let persisted = graph.to_persisted(&test_handle(0));
+        assert!(persisted.relations.iter().map(String::len).sum::<usize>() <= MAX_BYTES);
+        let loaded = RelationStorage::from_persisted(persisted);
+        assert!(loaded.forward[&test_handle(0)].contains(&kept));
+    }
+}
@@ -7,14 +7,35 @@ use std::{
 use anyhow::{bail, ensure, Context, Result};
 
 const HANDLE_LENGTH: usize = 32;
+/// The number of hex characters in a whole handle.
+pub(crate) const HEX_LENGTH: usize = 2 * HANDLE_LENGTH;
+
+// Fix packs the metadata of a handle into its last byte:
+//
+// | bits 7-6     | bit 5   | bit 4 | bits 3-0 |
+// | content type | literal | ref   | unused   |
+//
+// Literals store their data in the first 30 bytes, so bit 4 and the unused
+// bits instead hold the size of the data (0 to 30 bytes).
+// Everything else stores a hash in bytes 0-23 and a little-endian 48 bit size
+// in bytes 24-29.
+const METADATA_INDEX: usize = HANDLE_LENGTH - 1;
+const CONTENT_TYPE_SHIFT: u8 = 6;
+const LITERAL_BIT: u8 = 1 << 5;
+const REF_BIT: u8 = 1 << 4;
+const LITERAL_SIZE_MASK: u8 = 0b1_1111;
+const SIZE_RANGE: std::ops::Range<usize> = 24..30;
+/// The number of hex characters shown for abbreviated handles.
+const SHORT_HEX_LENGTH: usize = 8;
 
 #[derive(Debug, PartialEq, serde::Deserialize, serde::Serialize, Clone)]
+#[allow(dead_code)]
 pub(crate) struct Task {
     pub(crate) handle: Handle,
     pub(crate) operation: Operation,
 }
 
-#[derive(Debug, PartialEq, serde::Deserialize, serde::Serialize, Clone, Copy)]
+#[derive(Debug, PartialEq, Eq, Hash, serde::Deserialize, serde::Serialize, Clone, Copy)]
 #[repr(u8)]
 pub(crate) enum Operation {
     Eval = 0,
@@ -40,20 +61,159 @@ pub(crate) struct Handle {
     pub(crate) content: [u8; HANDLE_LENGTH],
 }
 
+/// The type of object a handle names.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub(crate) enum ContentType {
+    Blob,
+    Tree,
+    Tag,
+    Thunk,
+}
+
+/// The metadata Fix packs into a handle.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub(crate) enum HandleKind {
+    /// A blob whose data is stored in the handle itself.
+    Literal { size: usize },
+    /// An object whose contents can be fetched.
+    Object {
+        content_type: ContentType,
+        size: u64,
+    },
+    /// A reference to an object whose contents are not necessarily available.
+    Ref {
+        content_type: ContentType,
+        size: u64,
+    },
+}
+
+impl HandleKind {
+    pub(crate) fn content_type(&self) -> ContentType {
+        match self {
+            HandleKind::Literal { .. } => ContentType::Blob,
+            HandleKind::Object { content_type, .. } | HandleKind::Ref { content_type, .. } => {
+                *content_type
+            }
+        }
+    }
+
+    /// The number of bytes in a blob, or the number of entries in a tree.
+    pub(crate) fn size(&self) -> u64 {
+        match self {
+            HandleKind::Literal { size } => *size as u64,
+            HandleKind::Object { size, .. } | HandleKind::Ref { size, .. } => *size,
+        }
+    }
+}
+
+/// The value of a literal, decoded without asking the server.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub(crate) enum Literal {
+    /// A little-endian unsigned integer of `width` bytes.
+    Integer {
+        value: u64,
+        width: usize,
+    },
+    String(String),
+    Bytes(Vec<u8>),
+}
+
+impl Literal {
+    /// Literals are untyped, so guess the most likely interpretation.
+    fn decode(data: &[u8]) -> Self {
+        if let Ok(s) = std::str::from_utf8(data) {
+            if !s.is_empty() && !s.chars().any(char::is_control) {
+                return Literal::String(s.to_owned());
+            }
+        }
+        match data.len() {
+            1 | 2 | 4 | 8 => {
+                let mut bytes = [0_u8; 8];
+                bytes[..data.len()].copy_from_slice(data);
+                Literal::Integer {
+                    value: u64::from_le_bytes(bytes),
+                    width: data.len(),
+                }
+            }
+            _ => Literal::Bytes(data.to_vec()),
+        }
+    }
+}
+
 impl Handle {
+    /// Decodes the metadata of the handle.
+    pub(crate) fn kind(&self) -> HandleKind {
+        let metadata = self.content[METADATA_INDEX];
+        let content_type = match metadata >> CONTENT_TYPE_SHIFT {
+            0 => ContentType::Blob,
+            1 => ContentType::Tree,
+            2 => ContentType::Tag,
+            _ => ContentType::Thunk,
+        };
+        if metadata & LITERAL_BIT != 0 {
+            return HandleKind::Literal {
+                size: (metadata & LITERAL_SIZE_MASK) as usize,
+            };
+        }
+        let size = self.content[SIZE_RANGE]
+            .iter()
+            .rev()
+            .fold(0, |size, byte| (size << 8) | *byte as u64);
+        if metadata & REF_BIT != 0 {
+            HandleKind::Ref { content_type, size }
+        } else {
+            HandleKind::Object { content_type, size }
+        }
+    }
+
+    pub(crate) fn get_content_type(&self) -> ContentType {
+        self.kind().content_type()
+    }
+
+    /// The data stored in the handle, if it is a literal.
+    pub(crate) fn literal_data(&self) -> Option<&[u8]> {
+        match self.kind() {
+            HandleKind::Literal { size } => self.content.get(..size),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn is_literal(&self) -> bool {
+        matches!(self.kind(), HandleKind::Literal { .. })
+    }
+
+    /// The value stored in the handle, if it is a literal.
+    pub(crate) fn literal(&self) -> Option<Literal> {
+        self.literal_data().map(Literal::decode)
+    }
+
     /// Parses a handle in format 64 character hex string
-    pub(crate) fn from_hex(mut input: &str) -> Result<Self> {
+    pub(crate) fn from_hex(input: &str) -> Result<Self> {
         ensure!(input.len() == 64, "handle must be 64 hex characters");
         let mut content = [0_u8; HANDLE_LENGTH];
 
-        for byte in &mut content {
-            let (value, remaining) = input.split_at(2);
-            input = remaining;
-            *byte = u8::from_str_radix(value, 16).context("handle contains non-hex characters")?;
+        // Bytes rather than chars, so that other characters fail to parse
+        // instead of splitting a char.
+        for (byte, digits) in content.iter_mut().zip(input.as_bytes().chunks(2)) {
+            *byte = std::str::from_utf8(digits)
+                .ok()
+                .filter(|digits| digits.bytes().all(|b| b.is_ascii_hexdigit()))
+                .and_then(|digits| u8::from_str_radix(digits, 16).ok())
+                .context("handle contains non-hex characters")?;
         }
         Ok(Self { content })
     }
 
+    /// Whether the hex of the handle starts with `prefix`, in either case.
+    pub(crate) fn has_hex_prefix(&self, prefix: &str) -> bool {
+        prefix.len() <= 2 * HANDLE_LENGTH
+            && prefix.bytes().enumerate().all(|(i, digit)| {
+                let byte = self.content[i / 2];
+                let nibble = if i % 2 == 0 { byte >> 4 } else { byte & 0xf };
+                (digit as char).to_digit(16) == Some(u32::from(nibble))
+            })
+    }
+
     /// Reconstructs the hex string version of a Handle
     pub(crate) fn to_hex(&self) -> String {
         self.content.iter().fold(String::new(), |mut s, i| {
@@ -61,6 +221,21 @@ impl Handle {
             s
         })
     }
+
+    /// The start of the hex, which is usually enough to tell handles apart
+    /// and can be typed wherever a handle is expected.
+    pub(crate) fn short_hex(&self) -> String {
+        self.to_hex()[..SHORT_HEX_LENGTH].to_owned()
+    }
+}
+
+/// A handle made of the byte `i` repeated, for tests which only need
+/// distinct handles.
+#[cfg(test)]
+pub(crate) fn test_handle(i: u8) -> Handle {
+    Handle {
+        content: [i; HANDLE_LENGTH],
+    }
 }
 
 impl Display for Operation {
@@ -72,6 +247,46 @@ impl Display for Operation {
     }
 }
 
+impl Display for ContentType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            ContentType::Blob => "blob",
+            ContentType::Tree => "tree",
+            ContentType::Tag => "tag",
+            ContentType::Thunk => "thunk",
+        })
+    }
+}
+
+impl Display for HandleKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let unit = match self.content_type() {
+            ContentType::Blob => "bytes",
+            ContentType::Tree | ContentType::Tag => "entries",
+            ContentType::Thunk => "size",
+        };
+        let size = self.size();
+        match self {
+            HandleKind::Literal { .. } => write!(f, "literal blob ({size} {unit})"),
+            HandleKind::Object { content_type, .. } => {
+                write!(f, "{content_type} object ({size} {unit})")
+            }
+            HandleKind::Ref { content_type, .. } => write!(f, "{content_type} ref ({size} {unit})"),
+        }
+    }
+}
+
+impl Display for Literal {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Literal::Integer { value, width } => write!(f, "uint{}:{}", width * 8, value),
+            Literal::String(s) => write!(f, "{s:?}"),
+            Literal::Bytes(bytes) if bytes.is_empty() => f.write_str("empty"),
+            Literal::Bytes(bytes) => bytes.iter().try_for_each(|b| write!(f, "{b:02x}")),
+        }
+    }
+}
+
 impl FromStr for Operation {
     type Err = anyhow::Error;
 
@@ -83,3 +298,146 @@ impl FromStr for Operation {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn handle(metadata: u8, size: u64) -> Handle {
+        let mut content = [0xab; HANDLE_LENGTH];
+        content[SIZE_RANGE].copy_from_slice(&size.to_le_bytes()[..6]);
+        content[METADATA_INDEX] = metadata;
+        Handle { content }
+    }
+
+    #[test]
+    fn hex_round_trip() {
+        let hex = "1000000000000000000000000000000000000000000000000000000000000024";
+        let handle = Handle::from_hex(hex).unwrap();
+        assert_eq!(handle.content[0], 0x10);
+        assert_eq!(handle.content[METADATA_INDEX], 0x24);
+        assert_eq!(handle.to_hex(), hex);
+        assert!(Handle::from_hex(&hex[1..]).is_err());
+        assert!(Handle::from_hex(&hex.replace('1', "g")).is_err());
+        // 64 bytes, but not 64 characters.
+        assert!(Handle::from_hex(&format!("aé{}", &hex[3..])).is_err());
+        assert!(Handle::from_hex(&format!("+1{}", &hex[2..])).is_err());
+        assert!(handle.has_hex_prefix("10000"));
+        assert!(handle.has_hex_prefix(""));
+        assert!(!handle.has_hex_prefix("100001"));
+    }
+
+    #[test]
+    fn default_handle_is_literal() {
+        let handle =
+            Handle::from_hex("1000000000000000000000000000000000000000000000000000000000000024")
+                .unwrap();
+        assert_eq!(handle.kind(), HandleKind::Literal { size: 4 });
+        assert_eq!(handle.get_content_type(), ContentType::Blob);
+        assert_eq!(handle.kind().size(), 4);
+        assert_eq!(handle.literal_data(), Some(&[0x10, 0, 0, 0][..]));
+    }
+
+    #[test]
+    fn decodes_objects() {
+        assert_eq!(
+            handle(0b0000_0000, 1 << 20).kind(),
+            HandleKind::Object {
+                content_type: ContentType::Blob,
+                size: 1 << 20
+            }
+        );
+        assert_eq!(
+            handle(0b0100_0000, 3).kind(),
+            HandleKind::Object {
+                content_type: ContentType::Tree,
+                size: 3
+            }
+        );
+        assert_eq!(
+            handle(0b1000_0000, 3).kind(),
+            HandleKind::Object {
+                content_type: ContentType::Tag,
+                size: 3
+            }
+        );
+        assert_eq!(
+            handle(0b1100_0000, 0xffff_ffff_ffff).kind(),
+            HandleKind::Object {
+                content_type: ContentType::Thunk,
+                size: 0xffff_ffff_ffff
+            }
+        );
+    }
+
+    #[test]
+    fn decodes_refs() {
+        assert_eq!(
+            handle(0b0101_0000, 2).kind(),
+            HandleKind::Ref {
+                content_type: ContentType::Tree,
+                size: 2
+            }
+        );
+        assert_eq!(
+            handle(0b0001_0000, 100).kind(),
+            HandleKind::Ref {
+                content_type: ContentType::Blob,
+                size: 100
+            }
+        );
+    }
+
+    #[test]
+    fn literal_size_uses_ref_bit() {
+        assert_eq!(
+            handle(0b0011_1110, 0).kind(),
+            HandleKind::Literal { size: 30 }
+        );
+        assert_eq!(
+            handle(0b0010_0000, 0).kind(),
+            HandleKind::Literal { size: 0 }
+        );
+    }
+
+    #[test]
+    fn decodes_literals() {
+        let literal = |data: &[u8]| {
+            let mut content = [0; HANDLE_LENGTH];
+            content[..data.len()].copy_from_slice(data);
+            content[METADATA_INDEX] = LITERAL_BIT | data.len() as u8;
+            Handle { content }.literal()
+        };
+        assert_eq!(
+            literal(&[9, 0, 0, 0]),
+            Some(Literal::Integer { value: 9, width: 4 })
+        );
+        assert_eq!(
+            literal(&1_000_000_u64.to_le_bytes()),
+            Some(Literal::Integer {
+                value: 1_000_000,
+                width: 8
+            })
+        );
+        assert_eq!(
+            literal(b"compile-encode"),
+            Some(Literal::String("compile-encode".to_owned()))
+        );
+        assert_eq!(literal(&[1, 2, 3]), Some(Literal::Bytes(vec![1, 2, 3])));
+        assert_eq!(literal(&[]).unwrap().to_string(), "empty");
+        assert_eq!(literal(&[9, 0, 0, 0]).unwrap().to_string(), "uint32:9");
+        assert_eq!(handle(0b0100_0000, 3).literal(), None);
+    }
+
+    #[test]
+    fn displays_kind() {
+        assert_eq!(
+            handle(0b0100_0000, 3).kind().to_string(),
+            "tree object (3 entries)"
+        );
+        assert_eq!(
+            handle(0b0010_0100, 0).kind().to_string(),
+            "literal blob (4 bytes)"
+        );
+    }
+}
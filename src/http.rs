@@ -1,3 +1,11 @@
+mod cache;
+mod error;
+#[cfg(test)]
+pub(crate) mod mock;
+mod session;
+#[cfg(test)]
+mod tests;
+
 use std::sync::{
     atomic::{AtomicUsize, Ordering},
     mpsc::{self, Receiver, Sender},
@@ -8,39 +16,95 @@ use anyhow::{bail, ensure, Context, Result};
 use reqwest::Client;
 
 use crate::{
-    graphs::{Relation, RelationRhs},
-    handle::{Handle, Operation},
+    clock,
+    graphs::{blob::PAGE_SIZE, Relation, RelationRhs, RelationStorage},
+    handle::{ContentType, Handle, Operation},
+};
+
+use self::cache::{Lookup, Reuse};
+pub(crate) use self::{
+    cache::RequestCache,
+    error::{ErrorKind, HttpError},
+    session::{is_session_file, Session, SessionPanel},
 };
 
+/// The most lines kept in the console.
+const MAX_LOG_LINES: usize = 500;
+
+/// How long native requests wait for a response.
+#[cfg(not(target_arch = "wasm32"))]
+const TIMEOUT: std::time::Duration = std::time::Duration::from_secs(30);
+
 #[derive(Clone)]
 pub(crate) struct HttpContext {
     pub client: Arc<Client>,
     pub egui_ctx: egui::Context,
     pub url_base: String,
-    pub tx: Sender<Result<Vec<(usize, LogEntry)>>>,
+    pub tx: Sender<HttpMessage>,
     pub counter: Arc<AtomicUsize>,
+    pub cache: Arc<RequestCache>,
+    pub session: Arc<Session>,
+    /// Set while viewing a snapshot without a server, which fails every request.
+    pub offline: bool,
 }
 
 pub(crate) struct HttpLog {
-    pub tx: Sender<Result<Vec<(usize, LogEntry)>>>,
-    pub rx: Receiver<Result<Vec<(usize, LogEntry)>>>,
-    pub log: Vec<(usize, LogEntry)>,
+    pub tx: Sender<HttpMessage>,
+    pub rx: Receiver<HttpMessage>,
+    pub log: Vec<LogLine>,
     pub command_input: String,
+    /// Completions of the word being typed, when there are several.
+    pub completions: Vec<String>,
 }
 
-#[derive(Clone)]
-pub(crate) enum LogEntry {
-    // TODO: have enum for request types
-    Request(String),
-    Response(Relation),
+/// Progress of a request, identified by its id. Every request is
+/// `Sent` and then `Received` exactly once. Times are from [`clock::now`],
+/// taken when the request was sent and when its result came back.
+pub(crate) enum HttpMessage {
+    /// The command, and the request it was parsed into if it was valid.
+    Sent(usize, String, Option<Request>, f64),
+    /// The status of the response, sent before `Received` when the server
+    /// answered.
+    Status(usize, u16),
+    /// The id of the identical request in flight that this one was merged
+    /// into, sent before `Received`.
+    Merged(usize, usize),
+    Received(usize, Result<Vec<Relation>, HttpError>, f64),
 }
 
-#[derive(Clone)]
+/// A line in the console.
+pub(crate) enum LogLine {
+    Request(LogEntry),
+    /// Text written by the console itself, such as help.
+    Text(String),
+}
+
+/// A request and what came of it.
+pub(crate) struct LogEntry {
+    pub id: usize,
+    pub command: String,
+    /// The parsed request, which is missing if the command was invalid.
+    pub request: Option<Request>,
+    /// Seconds since the Unix epoch.
+    pub sent: f64,
+    pub completed: Option<f64>,
+    pub status: Option<u16>,
+    /// The request whose result this one shares.
+    pub merged_into: Option<usize>,
+    /// The relations the request produced, or why it failed.
+    pub result: Option<Result<Vec<Relation>, HttpError>>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub(crate) enum Request {
     Explanations(Handle),
     Contents(Handle),
     Description(Handle),
     Relations(Handle, Operation),
+    PinsAndTags(Handle),
+    TagContents(Handle),
+    /// The bytes of a blob starting at an offset, with a maximum length.
+    BlobContents(Handle, u64, u64),
 }
 
 impl Request {
@@ -50,10 +114,14 @@ impl Request {
             Request::Contents(h) => format!("contents {}", h.to_hex()),
             Request::Description(h) => format!("description {}", h.to_hex()),
             Request::Relations(h, o) => format!("relations {} {}", h.to_hex(), o),
+            Request::PinsAndTags(h) => format!("pins {}", h.to_hex()),
+            Request::TagContents(h) => format!("tag {}", h.to_hex()),
+            Request::BlobContents(h, offset, length) => {
+                format!("blob {} {} {}", h.to_hex(), offset, length)
+            }
         }
     }
 
-    // TODO: check for help and print message first.
     pub(crate) fn from_cli(str: &str) -> Result<Self> {
         let mut args = str.split_whitespace();
         let Some(first) = args.next() else {
@@ -74,6 +142,21 @@ impl Request {
             "explanations" => take_handle(&mut args, 1).map(Request::Explanations),
             "contents" => take_handle(&mut args, 1).map(Request::Contents),
             "description" => take_handle(&mut args, 1).map(Request::Description),
+            "pins" => take_handle(&mut args, 1).map(Request::PinsAndTags),
+            "tag" => take_handle(&mut args, 1).map(Request::TagContents),
+            "blob" => take_handle(&mut args, 1).and_then(|h| {
+                let mut take_number = |position: usize, default: u64| {
+                    args.next().map_or(Ok(default), |n| {
+                        n.parse::<u64>()
+                            .with_context(|| format!("Invalid number at position {position}"))
+                    })
+                };
+                Ok(Request::BlobContents(
+                    h,
+                    take_number(2, 0)?,
+                    take_number(3, PAGE_SIZE)?,
+                ))
+            }),
             "relations" => take_handle(&mut args, 1).and_then(|h| {
                 Ok(Request::Relations(
                     h,
@@ -82,35 +165,77 @@ impl Request {
                         .and_then(|o| o.parse::<Operation>().context("at position 2"))?,
                 ))
             }),
+            "eval" => take_handle(&mut args, 1).map(|h| Request::Relations(h, Operation::Eval)),
+            "apply" => take_handle(&mut args, 1).map(|h| Request::Relations(h, Operation::Apply)),
             _ => bail!("Invalid argument at position 0 {}", first),
         }
     }
 
+    /// The handle the request is about.
+    pub(crate) fn handle(&self) -> &Handle {
+        match self {
+            Request::Explanations(h)
+            | Request::Contents(h)
+            | Request::Description(h)
+            | Request::Relations(h, _)
+            | Request::PinsAndTags(h)
+            | Request::TagContents(h)
+            | Request::BlobContents(h, _, _) => h,
+        }
+    }
+
+    /// Whether the response can change over time, as the server computes
+    /// more, unlike responses about the objects themselves.
+    pub(crate) fn can_change(&self) -> bool {
+        matches!(self, Request::Explanations(_) | Request::PinsAndTags(_))
+    }
+
     fn to_url_path(&self) -> String {
         match self {
             Request::Explanations(h) => format!("/explanations?handle={}", h.to_hex()),
-            // TODO: handle other types of content
             Request::Contents(h) => format!("/tree_contents?handle={}", h.to_hex()),
             Request::Description(h) => format!("/description?handle={}", h.to_hex()),
             Request::Relations(h, o) => format!("/relation?handle={}&op={}", h.to_hex(), *o as u8),
+            // Unverified: the Fixpoint server's pin and tag listing is
+            // assumed to live here and to answer like `/tree_contents`, with
+            // `{"handles": [...]}` listing the objects pinning or tagging `h`.
+            // Only the mock server is known to serve it.
+            Request::PinsAndTags(h) => format!("/pins_and_tags?handle={}", h.to_hex()),
+            // Tags are trees of exactly three entries.
+            Request::TagContents(h) => format!("/tree_contents?handle={}", h.to_hex()),
+            // Unverified like `/pins_and_tags`: assumed to answer with
+            // `{"contents": "<hex>"}`, holding at most `length` bytes of the
+            // blob from `offset`.
+            Request::BlobContents(h, offset, length) => format!(
+                "/blob_contents?handle={}&offset={}&length={}",
+                h.to_hex(),
+                offset,
+                length
+            ),
         }
     }
 
-    async fn parse(&self, response: reqwest::Response) -> Result<Vec<Relation>> {
-        async fn to_json<T: for<'a> serde::Deserialize<'a>>(
-            response: reqwest::Response,
-        ) -> Result<T> {
-            response.json::<T>().await.context("parsing json")
+    /// Parses a response to this request.
+    fn parse(&self, status: u16, body: &str) -> Result<Vec<Relation>, ErrorKind> {
+        fn to_json<T: for<'a> serde::Deserialize<'a>>(body: &str) -> Result<T, ErrorKind> {
+            serde_json::from_str(body).map_err(|e| ErrorKind::Json(e.to_string()))
+        }
+        if !(200..300).contains(&status) {
+            return Err(ErrorKind::Status {
+                status,
+                body: body.to_owned(),
+            });
         }
         let mut results = vec![];
         match self {
             Request::Explanations(_) => {
                 #[derive(serde::Deserialize)]
                 struct JsonResponse {
+                    #[allow(dead_code)]
                     target: String,
                     relations: EmptyStringOrVec<JsonRelation>,
                 }
-                let json = to_json::<JsonResponse>(response).await?;
+                let json = to_json::<JsonResponse>(body)?;
                 if let EmptyStringOrVec::Vec(relations) = json.relations {
                     for relation in relations {
                         let rhs = parse_handle(&relation.rhs)?;
@@ -130,14 +255,14 @@ impl Request {
                 struct JsonResponse {
                     handles: EmptyStringOrVec<String>,
                 }
-                let json = to_json::<JsonResponse>(response).await?;
+                let json = to_json::<JsonResponse>(body)?;
                 let EmptyStringOrVec::Vec(entries) = json.handles else {
                     return Ok(vec![]);
                 };
                 let entries = entries
                     .into_iter()
                     .map(parse_handle)
-                    .collect::<Result<Vec<_>>>()?;
+                    .collect::<Result<Vec<_>, _>>()?;
                 results = entries
                     .into_iter()
                     .enumerate()
@@ -150,16 +275,18 @@ impl Request {
                 struct JsonResponse {
                     description: String,
                 }
-                let json = to_json::<JsonResponse>(response).await?;
+                let json = to_json::<JsonResponse>(body)?;
                 results = vec![Relation::new(
                     h.clone(),
                     RelationRhs::Description(json.description),
                 )];
             }
             Request::Relations(h, o) => {
-                let json = to_json::<JsonRelation>(response).await?;
+                let json = to_json::<JsonRelation>(body)?;
                 let op = parse_op(json.op)?;
-                ensure!(op == *o, "got different op back than requested");
+                if op != *o {
+                    return Err(ErrorKind::InvalidOp(format!("got {op} back for {o}")));
+                }
                 results = vec![Relation::new(
                     h.clone(),
                     match op {
@@ -168,42 +295,125 @@ impl Request {
                     }(parse_handle(json.rhs)?),
                 )];
             }
+            Request::PinsAndTags(h) => {
+                #[derive(serde::Deserialize)]
+                struct JsonResponse {
+                    handles: EmptyStringOrVec<String>,
+                }
+                let json = to_json::<JsonResponse>(body)?;
+                let EmptyStringOrVec::Vec(handles) = json.handles else {
+                    return Ok(vec![]);
+                };
+                results = handles
+                    .into_iter()
+                    .map(|p| Ok(Relation::new(parse_handle(p)?, RelationRhs::Pin(h.clone()))))
+                    .collect::<Result<_, _>>()?;
+            }
+            Request::TagContents(h) => {
+                #[derive(serde::Deserialize)]
+                struct JsonResponse {
+                    handles: EmptyStringOrVec<String>,
+                }
+                let json = to_json::<JsonResponse>(body)?;
+                let EmptyStringOrVec::Vec(handles) = json.handles else {
+                    return Err(ErrorKind::Json("expected tag to have children".to_owned()));
+                };
+                let handles = handles
+                    .into_iter()
+                    .map(parse_handle)
+                    .collect::<Result<Vec<_>, _>>()?;
+                let [target, author, label]: [Handle; 3] =
+                    handles.try_into().map_err(|e: Vec<_>| {
+                        ErrorKind::Json(format!(
+                            "expected tag to have three children, found {}",
+                            e.len()
+                        ))
+                    })?;
+                results = vec![
+                    Relation::new(h.clone(), RelationRhs::TagTarget(target)),
+                    Relation::new(h.clone(), RelationRhs::TagAuthor(author)),
+                    Relation::new(h.clone(), RelationRhs::TagLabel(label)),
+                ];
+            }
+            Request::BlobContents(h, offset, _) => {
+                // Bytes are hex encoded to survive the property tree serialization.
+                #[derive(serde::Deserialize)]
+                struct JsonResponse {
+                    contents: String,
+                }
+                let json = to_json::<JsonResponse>(body)?;
+                results = vec![Relation::new(
+                    h.clone(),
+                    RelationRhs::BlobChunk(
+                        *offset,
+                        parse_hex_bytes(&json.contents)
+                            .map_err(|e| ErrorKind::Json(format!("{e:#}")))?,
+                    ),
+                )];
+            }
         }
         Ok(results)
     }
 
-    pub(crate) fn parse_send(request: String, ctx: HttpContext) {
+    /// Sends a request in the background, returning its id.
+    /// Cached responses are reused, even for requests whose response can
+    /// change over time. Use [`refresh`](Self::refresh) to fetch those again.
+    pub(crate) fn parse_send(request: String, ctx: HttpContext) -> usize {
+        Self::parse_send_with(request, ctx, Reuse::Cache)
+    }
+
+    fn parse_send_with(request: String, ctx: HttpContext, reuse: Reuse) -> usize {
         let count = ctx.counter.fetch_add(1, Ordering::SeqCst);
-        let _ = ctx
-            .tx
-            .send(Ok(vec![(count, LogEntry::Request(request.clone()))]));
-        let request = match Self::from_cli(&request) {
+        let parsed = Self::from_cli(&request);
+        let _ = ctx.tx.send(HttpMessage::Sent(
+            count,
+            request,
+            parsed.as_ref().ok().cloned(),
+            clock::now(),
+        ));
+        let fail = |kind| {
+            let error = HttpError { id: count, kind };
+            let _ = ctx
+                .tx
+                .send(HttpMessage::Received(count, Err(error), clock::now()));
+            count
+        };
+        let request = match parsed {
             Ok(v) => v,
-            Err(e) => {
-                let _ = ctx.tx.send(Err(e.context("parsing cli")));
-                return;
-            }
+            Err(e) => return fail(ErrorKind::Command(format!("{e:#}"))),
         };
+        if ctx.offline {
+            return fail(ErrorKind::Offline);
+        }
+        match ctx.cache.lookup(&ctx.url_base, &request, count, reuse) {
+            Lookup::Send => {}
+            Lookup::Merged(into) => {
+                let _ = ctx.tx.send(HttpMessage::Merged(count, into));
+                return count;
+            }
+            Lookup::Cached(relations) => {
+                let _ = ctx
+                    .tx
+                    .send(HttpMessage::Received(count, Ok(relations), clock::now()));
+                return count;
+            }
+        }
         let task = async move {
-            let result = ctx
-                .client
-                .get(format!("http://{}{}", ctx.url_base, request.to_url_path()))
-                .send()
-                .await;
-            match result {
-                Ok(ok) => {
-                    let _ = ctx.tx.send(request.parse(ok).await.map(|v| {
-                        v.into_iter()
-                            .map(|r| (count, LogEntry::Response(r)))
-                            .collect()
-                    }));
-                }
-                Err(e) => {
-                    let _ = ctx
-                        .tx
-                        .send(Err(anyhow::anyhow!(format!("request failed: {}", e))));
+            let response = match ctx.session.replay(&request) {
+                Some(response) => response,
+                None => {
+                    let response = fetch(&ctx.client, &ctx.url_base, &request).await;
+                    ctx.session.record(&request, &response);
+                    response
                 }
+            };
+            if let Ok((status, _)) = &response {
+                let _ = ctx.tx.send(HttpMessage::Status(count, *status));
             }
+            let result = response
+                .and_then(|(status, body)| request.parse(status, &body))
+                .map_err(|kind| HttpError { id: count, kind });
+            request.complete(&ctx, count, result);
             ctx.egui_ctx.request_repaint();
         };
         #[cfg(target_arch = "wasm32")]
@@ -211,11 +421,45 @@ impl Request {
         #[cfg(not(target_arch = "wasm32"))]
         #[allow(clippy::let_underscore_future)]
         let _ = tokio::spawn(task);
+        count
     }
 
-    pub(crate) fn send(self, ctx: HttpContext) {
+    pub(crate) fn send(self, ctx: HttpContext) -> usize {
         Self::parse_send(self.to_cli(), ctx)
     }
+
+    /// Sends the request, unless its response is cached or `graph` already
+    /// holds it, as [`RelationStorage::answer`] decides.
+    pub(crate) fn send_unless_known(self, ctx: HttpContext, graph: &RelationStorage) -> usize {
+        Self::parse_send_with(self.to_cli(), ctx, Reuse::Known(graph))
+    }
+
+    /// Sends the request even if its response is cached, replacing the
+    /// cached response.
+    pub(crate) fn refresh(self, ctx: HttpContext) -> usize {
+        Self::parse_send_with(self.to_cli(), ctx, Reuse::Nothing)
+    }
+
+    /// Sends the request straight to the server and parses the response,
+    /// bypassing the cache and session of the viewer.
+    pub(crate) async fn get(
+        &self,
+        client: &Client,
+        url_base: &str,
+    ) -> Result<Vec<Relation>, ErrorKind> {
+        let (status, body) = fetch(client, url_base, self).await?;
+        self.parse(status, &body)
+    }
+
+    /// Reports the result to the request and every request merged into it.
+    fn complete(&self, ctx: &HttpContext, id: usize, result: Result<Vec<Relation>, HttpError>) {
+        let time = clock::now();
+        for waiting in ctx.cache.complete(&ctx.url_base, self, &result) {
+            let copy = result.clone().map_err(|e| HttpError { id: waiting, ..e });
+            let _ = ctx.tx.send(HttpMessage::Received(waiting, copy, time));
+        }
+        let _ = ctx.tx.send(HttpMessage::Received(id, result, time));
+    }
 }
 
 impl HttpLog {
@@ -226,8 +470,97 @@ impl HttpLog {
             rx,
             log: vec![],
             command_input: String::new(),
+            completions: vec![],
         }
     }
+
+    pub(crate) fn print(&mut self, text: String) {
+        self.push(LogLine::Text(text));
+    }
+
+    /// Adds a line, dropping the oldest beyond [`MAX_LOG_LINES`].
+    fn push(&mut self, line: LogLine) {
+        self.log.push(line);
+        let excess = self.log.len().saturating_sub(MAX_LOG_LINES);
+        self.log.drain(..excess);
+    }
+
+    /// Records a message about a request.
+    pub(crate) fn on_message(&mut self, message: &HttpMessage) {
+        match message {
+            HttpMessage::Sent(id, command, request, time) => {
+                self.push(LogLine::Request(LogEntry {
+                    id: *id,
+                    command: command.clone(),
+                    request: request.clone(),
+                    sent: *time,
+                    completed: None,
+                    status: None,
+                    merged_into: None,
+                    result: None,
+                }))
+            }
+            HttpMessage::Status(id, status) => {
+                if let Some(entry) = self.entry(*id) {
+                    entry.status = Some(*status);
+                }
+            }
+            HttpMessage::Merged(id, into) => {
+                if let Some(entry) = self.entry(*id) {
+                    entry.merged_into = Some(*into);
+                }
+            }
+            HttpMessage::Received(id, result, time) => {
+                if let Some(entry) = self.entry(*id) {
+                    if let Err(HttpError {
+                        kind: ErrorKind::Status { status, .. },
+                        ..
+                    }) = result
+                    {
+                        entry.status = Some(*status);
+                    }
+                    entry.completed = Some(*time);
+                    entry.result = Some(result.clone());
+                }
+            }
+        }
+    }
+
+    fn entry(&mut self, id: usize) -> Option<&mut LogEntry> {
+        // Requests are usually completed soon after they are sent.
+        self.log.iter_mut().rev().find_map(|line| match line {
+            LogLine::Request(entry) if entry.id == id => Some(entry),
+            _ => None,
+        })
+    }
+}
+
+/// Gets the status and body of the response to a request.
+async fn fetch(
+    client: &Client,
+    url_base: &str,
+    request: &Request,
+) -> Result<(u16, String), ErrorKind> {
+    let response = client
+        .get(format!("{}{}", url_base, request.to_url_path()))
+        .send()
+        .await
+        .map_err(ErrorKind::from_reqwest)?;
+    let status = response.status().as_u16();
+    let body = response.text().await.map_err(ErrorKind::from_reqwest)?;
+    Ok((status, body))
+}
+
+/// Creates the client requests are sent with. Natively, requests time out
+/// after [`TIMEOUT`], while browsers apply their own timeouts.
+pub(crate) fn client() -> Client {
+    #[cfg(not(target_arch = "wasm32"))]
+    return Client::builder()
+        .timeout(TIMEOUT)
+        .build()
+        .unwrap_or_default();
+    #[cfg(target_arch = "wasm32")]
+    Client::new()
 }
 
 #[derive(serde::Deserialize)]
@@ -243,114 +576,43 @@ struct JsonRelation {
 #[derive(serde::Deserialize)]
 #[serde(untagged)]
 enum EmptyStringOrVec<T> {
-    String(String),
+    String(#[allow(dead_code)] String),
     Vec(Vec<T>),
 }
 
-fn parse_handle(handle: impl AsRef<str>) -> Result<Handle> {
-    Handle::from_hex(handle.as_ref()).with_context(|| format!("parsing {}", handle.as_ref()))
+fn parse_handle(handle: impl AsRef<str>) -> Result<Handle, ErrorKind> {
+    Handle::from_hex(handle.as_ref())
+        .map_err(|e| ErrorKind::InvalidHandle(format!("{}: {e:#}", handle.as_ref())))
+}
+
+fn parse_hex_bytes(hex: &str) -> Result<Vec<u8>> {
+    ensure!(hex.len() % 2 == 0, "odd number of hex characters");
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| {
+            hex.get(i..i + 2)
+                .and_then(|b| u8::from_str_radix(b, 16).ok())
+                .context("contents contain non-hex characters")
+        })
+        .collect()
 }
 
-fn parse_op(op: impl AsRef<str>) -> Result<Operation> {
+fn parse_op(op: impl AsRef<str>) -> Result<Operation, ErrorKind> {
     op.as_ref()
         .parse::<u8>()
-        .context("parsing op")
-        .and_then(|i| TryInto::<Operation>::try_into(i))
-        .context("parsing op")
+        .context("not a number")
+        .and_then(TryInto::<Operation>::try_into)
+        .map_err(|e| ErrorKind::InvalidOp(format!("{}: {e:#}", op.as_ref())))
 }
 
-pub(crate) fn get_pins_and_tags(ctx: HttpContext, target: &Handle, pins_and_tags: Vec<Handle>) {
-    // For every handle, if it is a tag, then fetch it.
-    // If the target handle is in the the first slot of the tag, then
-    // we use add a tag relation from the tag to the target.
-    // Otherwise, we add a pin relation from the handle to the target.
-
-    for handle in pins_and_tags {
-        let target = target.clone();
-        todo!()
-        // if handle.get_content_type() == Object::Tag {
-        //     get_tag_contents(ctx.clone(), &handle.clone(), Some(target.clone()));
-        // } else {
-        //     let _ = ctx.tx.send(Ok(vec![Relation::new(
-        //         target.clone(),
-        //         handle,
-        //         RelationType::Pin,
-        //     )]));
-        // }
-    }
+/// The requests for the contents of every tag among the objects pinning a
+/// target, which a [`Request::PinsAndTags`] response lists without their
+/// contents. Whoever receives the pins sends these, so they can be followed.
+pub(crate) fn tag_requests(relations: &[Relation]) -> Vec<Request> {
+    relations
+        .iter()
+        .filter(|r| matches!(r.rhs, RelationRhs::Pin(_)))
+        .filter(|r| r.lhs.get_content_type() == ContentType::Tag)
+        .map(|r| Request::TagContents(r.lhs.clone()))
+        .collect()
 }
-
-// pub(crate) fn get_tag_contents(ctx: HttpContext, handle: &Handle, with_pin_target: Option<Handle>) {
-//     #[derive(serde::Deserialize)]
-//     struct JsonResponse {
-//         handles: EmptyStringOrVec<String>,
-//     }
-
-//     let handle_c = handle.clone();
-
-//     get(
-//         ctx.clone(),
-//         move |json: JsonResponse| {
-//             let EmptyStringOrVec::Vec(handles) = json.handles else {
-//                 return Err(anyhow!("expected tag to have children"));
-//             };
-//             let handles = handles
-//                 .into_iter()
-//                 .map(parse_handle)
-//                 .collect::<Result<Vec<_>>>()?;
-//             let handles: [Handle; 3] = handles
-//                 .try_into()
-//                 .map_err(|e: Vec<_>| anyhow!("found {} handles", e.len()))
-//                 .context("expected tag to have three children")?;
-
-//             let mut result = vec![
-//                 Relation::new(
-//                     handle_c.clone(),
-//                     handles[0].clone(),
-//                     RelationType::TagTarget,
-//                 ),
-//                 Relation::new(
-//                     handle_c.clone(),
-//                     handles[1].clone(),
-//                     RelationType::TagAuthor,
-//                 ),
-//                 Relation::new(handle_c.clone(), handles[2].clone(), RelationType::TagLabel),
-//             ];
-//             if let Some(target) = with_pin_target {
-//                 result.push(Relation::new(handle_c.clone(), target, RelationType::Pin))
-//             }
-
-//             Ok(result)
-//         },
-//         format!("/tree_contents?handle={}", handle.to_hex()),
-//     )
-// }
-
-// pub(crate) fn get_contents(ctx: HttpContext, handle: &Handle) {
-//     // TODO handle blob, tag in one response
-//     #[derive(serde::Deserialize)]
-//     struct JsonResponse {
-//         handles: EmptyStringOrVec<String>,
-//     }
-
-//     let handle_clone = handle.clone();
-
-//     get(
-//         ctx.clone(),
-//         move |json: JsonResponse| {
-//             let EmptyStringOrVec::Vec(entries) = json.handles else {
-//                 return Ok(vec![]);
-//             };
-//             let entries = entries
-//                 .into_iter()
-//                 .map(parse_handle)
-//                 .collect::<Result<Vec<_>>>()?;
-//             Ok(entries
-//                 .into_iter()
-//                 .enumerate()
-//                 .map(|(i, e)| Relation::new(handle_clone.clone(), e, RelationType::TreeEntry(i)))
-//                 .collect())
-//         },
-//         format!("/contents?handle={}", handle.to_hex()),
-//     );
-// }
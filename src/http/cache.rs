@@ -0,0 +1,222 @@
+use std::{collections::HashMap, sync::Mutex};
+
+use crate::{
+    graphs::{Relation, RelationRhs, RelationStorage},
+    handle::Operation,
+};
+
+use super::{HttpError, Request};
+
+/// Responses to requests, which can be reused because handles are content
+/// addressed and operations are deterministic. Responses are kept per server,
+/// since another server may not know the same objects.
+#[derive(Default)]
+pub(crate) struct RequestCache {
+    entries: Mutex<HashMap<(String, Request), Entry>>,
+}
+
+enum Entry {
+    /// The id of the request in flight, and of identical requests waiting
+    /// on it.
+    InFlight(usize, Vec<usize>),
+    Done(Vec<Relation>),
+}
+
+/// What to do with a request, as decided by the cache.
+pub(crate) enum Lookup {
+    /// Send the request, and call [`RequestCache::complete`] with the result.
+    Send,
+    /// An identical request, with this id, is in flight, and its result will
+    /// be shared.
+    Merged(usize),
+    Cached(Vec<Relation>),
+}
+
+/// What a request can be answered from instead of the server.
+#[derive(Clone, Copy)]
+pub(crate) enum Reuse<'a> {
+    /// Earlier responses from the same server.
+    Cache,
+    /// Earlier responses, or the relations already in a graph.
+    Known(&'a RelationStorage),
+    /// Nothing, which refreshes responses that can change over time.
+    Nothing,
+}
+
+impl RequestCache {
+    /// Looks up a request to the server at `url_base`, answering it from
+    /// what `reuse` allows, or marking it as in flight if it has to be sent.
+    /// Even refreshed requests are merged with identical ones in flight.
+    pub(crate) fn lookup(
+        &self,
+        url_base: &str,
+        request: &Request,
+        id: usize,
+        reuse: Reuse,
+    ) -> Lookup {
+        let key = (url_base.to_owned(), request.clone());
+        let mut entries = self.entries.lock().unwrap();
+        match (entries.get_mut(&key), reuse) {
+            (Some(Entry::InFlight(sent, waiting)), _) => {
+                waiting.push(id);
+                return Lookup::Merged(*sent);
+            }
+            (_, Reuse::Nothing) => {}
+            (Some(Entry::Done(relations)), _) => return Lookup::Cached(relations.clone()),
+            (None, Reuse::Known(graph)) => {
+                if let Some(relations) = graph.answer(request) {
+                    return Lookup::Cached(relations);
+                }
+            }
+            (None, Reuse::Cache) => {}
+        }
+        entries.insert(key, Entry::InFlight(id, vec![]));
+        Lookup::Send
+    }
+
+    /// Stores the result of a request, returning the ids of the requests
+    /// that were merged into it.
+    pub(crate) fn complete(
+        &self,
+        url_base: &str,
+        request: &Request,
+        result: &Result<Vec<Relation>, HttpError>,
+    ) -> Vec<usize> {
+        let key = |request: Request| (url_base.to_owned(), request);
+        let mut entries = self.entries.lock().unwrap();
+        let waiting = match entries.remove(&key(request.clone())) {
+            Some(Entry::InFlight(_, waiting)) => waiting,
+            _ => vec![],
+        };
+        // Failures are not cached, so that they can be retried.
+        if let Ok(relations) = result {
+            // Any relation we learn about answers the request for it.
+            for relation in relations {
+                let implied = match relation.rhs {
+                    RelationRhs::Eval(_) => {
+                        Request::Relations(relation.lhs.clone(), Operation::Eval)
+                    }
+                    RelationRhs::Apply(_) => {
+                        Request::Relations(relation.lhs.clone(), Operation::Apply)
+                    }
+                    _ => continue,
+                };
+                if implied != *request {
+                    entries
+                        .entry(key(implied))
+                        .or_insert_with(|| Entry::Done(vec![relation.clone()]));
+                }
+            }
+            entries.insert(key(request.clone()), Entry::Done(relations.clone()));
+        }
+        waiting
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{handle::test_handle, http::ErrorKind};
+
+    const SERVER: &str = "http://127.0.0.1:9090";
+
+    #[test]
+    fn merges_requests_in_flight() {
+        let cache = RequestCache::default();
+        let request = Request::Contents(test_handle(0));
+        let lookup = |id| cache.lookup(SERVER, &request, id, Reuse::Cache);
+        assert!(matches!(lookup(0), Lookup::Send));
+        assert!(matches!(lookup(1), Lookup::Merged(0)));
+        assert!(matches!(lookup(2), Lookup::Merged(0)));
+        assert_eq!(cache.complete(SERVER, &request, &Ok(vec![])), vec![1, 2]);
+        assert!(matches!(lookup(3), Lookup::Cached(r) if r.is_empty()));
+        // Another server may not know the tree.
+        let other = cache.lookup("http://fix.example:9090", &request, 4, Reuse::Cache);
+        assert!(matches!(other, Lookup::Send));
+    }
+
+    #[test]
+    fn does_not_cache_failures() {
+        let cache = RequestCache::default();
+        let request = Request::Description(test_handle(0));
+        cache.lookup(SERVER, &request, 0, Reuse::Cache);
+        let error = HttpError {
+            id: 0,
+            kind: ErrorKind::Timeout,
+        };
+        cache.complete(SERVER, &request, &Err(error));
+        assert!(matches!(
+            cache.lookup(SERVER, &request, 1, Reuse::Cache),
+            Lookup::Send
+        ));
+    }
+
+    #[test]
+    fn explanations_answer_relations() {
+        let cache = RequestCache::default();
+        let request = Request::Explanations(test_handle(0));
+        let relation = Relation::new(test_handle(1), RelationRhs::Eval(test_handle(2)));
+        cache.lookup(SERVER, &request, 0, Reuse::Cache);
+        cache.complete(SERVER, &request, &Ok(vec![relation.clone()]));
+        assert!(matches!(
+            cache.lookup(SERVER, &request, 1, Reuse::Cache),
+            Lookup::Cached(_)
+        ));
+        // Explanations can change, so they can be refreshed.
+        assert!(matches!(
+            cache.lookup(SERVER, &request, 1, Reuse::Nothing),
+            Lookup::Send
+        ));
+        assert!(matches!(
+            cache.lookup(SERVER, &request, 4, Reuse::Cache),
+            Lookup::Merged(1)
+        ));
+        let lookup = cache.lookup(
+            SERVER,
+            &Request::Relations(test_handle(1), Operation::Eval),
+            2,
+            Reuse::Cache,
+        );
+        assert!(matches!(lookup, Lookup::Cached(r) if r == vec![relation]));
+        let lookup = cache.lookup(
+            SERVER,
+            &Request::Relations(test_handle(1), Operation::Apply),
+            3,
+            Reuse::Cache,
+        );
+        assert!(matches!(lookup, Lookup::Send));
+    }
+
+    #[test]
+    fn answers_from_the_graph() {
+        let cache = RequestCache::default();
+        let mut graph = RelationStorage::default();
+        let eval = Relation::new(test_handle(1), RelationRhs::Eval(test_handle(2)));
+        graph.insert(eval.clone());
+        graph.insert(Relation::new(
+            test_handle(1),
+            RelationRhs::TreeEntry(test_handle(3), 0),
+        ));
+        graph.insert(Relation::new(
+            test_handle(4),
+            RelationRhs::BlobChunk(256, vec![1]),
+        ));
+        let lookup = |request, id| cache.lookup(SERVER, &request, id, Reuse::Known(&graph));
+        let known = lookup(Request::Relations(test_handle(1), Operation::Eval), 0);
+        assert!(matches!(known, Lookup::Cached(r) if r == vec![eval]));
+        let contents = lookup(Request::Contents(test_handle(1)), 1);
+        assert!(matches!(contents, Lookup::Cached(r) if r.len() == 1));
+        let page = lookup(Request::BlobContents(test_handle(4), 256, 256), 1);
+        assert!(matches!(page, Lookup::Cached(r) if r.len() == 1));
+        let other_page = lookup(Request::BlobContents(test_handle(4), 0, 256), 1);
+        assert!(matches!(other_page, Lookup::Send));
+        for request in [
+            Request::Relations(test_handle(1), Operation::Apply),
+            Request::Description(test_handle(1)),
+            Request::Explanations(test_handle(2)),
+            Request::Contents(test_handle(2)),
+        ] {
+            assert!(matches!(lookup(request, 2), Lookup::Send));
+        }
+    }
+}
@@ -0,0 +1,74 @@
+use std::fmt::{self, Display};
+
+use reqwest::StatusCode;
+
+/// Why a request failed.
+#[derive(Clone, Debug, PartialEq, serde::Deserialize, serde::Serialize)]
+pub(crate) enum ErrorKind {
+    /// The command could not be parsed into a request.
+    Command(String),
+    /// Requests are disabled while viewing a snapshot.
+    Offline,
+    /// No response was received.
+    Connection(String),
+    Timeout,
+    /// The server responded with an error status.
+    Status {
+        status: u16,
+        body: String,
+    },
+    /// The response did not have the expected shape.
+    Json(String),
+    InvalidHandle(String),
+    InvalidOp(String),
+}
+
+/// A failed request, identified by the id it was sent with.
+#[derive(Clone, Debug)]
+pub(crate) struct HttpError {
+    pub id: usize,
+    pub kind: ErrorKind,
+}
+
+impl ErrorKind {
+    pub(crate) fn from_reqwest(error: reqwest::Error) -> Self {
+        #[cfg(not(target_arch = "wasm32"))]
+        if error.is_timeout() {
+            return ErrorKind::Timeout;
+        }
+        ErrorKind::Connection(error.to_string())
+    }
+}
+
+impl Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ErrorKind::Command(e) => write!(f, "invalid command: {e}"),
+            ErrorKind::Offline => f.write_str("not connected to a server while viewing a snapshot"),
+            ErrorKind::Connection(e) => write!(f, "request failed: {e}"),
+            ErrorKind::Timeout => f.write_str("request timed out"),
+            ErrorKind::Status { status, body } => {
+                let reason = StatusCode::from_u16(*status)
+                    .ok()
+                    .and_then(|s| s.canonical_reason())
+                    .unwrap_or_default();
+                write!(f, "server responded {status} {reason}")?;
+                match body.trim() {
+                    "" => Ok(()),
+                    body => write!(f, ": {body}"),
+                }
+            }
+            ErrorKind::Json(e) => write!(f, "unexpected response: {e}"),
+            ErrorKind::InvalidHandle(e) => write!(f, "invalid handle in response: {e}"),
+            ErrorKind::InvalidOp(e) => write!(f, "invalid op in response: {e}"),
+        }
+    }
+}
+
+impl Display for HttpError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "request #{}: {}", self.id, self.kind)
+    }
+}
+
+impl std::error::Error for HttpError {}
@@ -0,0 +1,149 @@
+use std::collections::HashMap;
+
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+};
+
+use crate::handle::{Handle, Operation};
+
+/// An in-memory object graph served like the Fixpoint HTTP server does.
+#[derive(Clone, Default)]
+pub(crate) struct MockGraph {
+    pub trees: HashMap<Handle, Vec<Handle>>,
+    pub descriptions: HashMap<Handle, String>,
+    pub relations: HashMap<(Handle, Operation), Handle>,
+    /// The pins and tags of each handle, which are none for handles missing
+    /// from here.
+    pub pins: HashMap<Handle, Vec<Handle>>,
+    pub blobs: HashMap<Handle, Vec<u8>>,
+}
+
+impl MockGraph {
+    /// Serves the graph on an unused port, returning the url to prefix
+    /// request paths with.
+    pub(crate) async fn serve(self) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url_base = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(self.clone().respond(stream));
+            }
+        });
+        url_base
+    }
+
+    async fn respond(self, mut stream: TcpStream) {
+        let mut request = vec![];
+        let mut buffer = [0; 1024];
+        while !request.ends_with(b"\r\n\r\n") {
+            match stream.read(&mut buffer).await {
+                Ok(0) | Err(_) => return,
+                Ok(n) => request.extend_from_slice(&buffer[..n]),
+            }
+        }
+        let request = String::from_utf8_lossy(&request);
+        let target = request.split_whitespace().nth(1).unwrap_or_default();
+        let (status, body) = self.route(target);
+        let response = format!(
+            "HTTP/1.1 {status}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+            body.len()
+        );
+        let _ = stream.write_all(response.as_bytes()).await;
+    }
+
+    fn route(&self, target: &str) -> (&'static str, String) {
+        let (path, query) = target.split_once('?').unwrap_or((target, ""));
+        let params: HashMap<&str, &str> = query
+            .split('&')
+            .filter_map(|pair| pair.split_once('='))
+            .collect();
+        let Some(handle) = params.get("handle").and_then(|h| Handle::from_hex(h).ok()) else {
+            return ("400 Bad Request", "missing or invalid handle".to_owned());
+        };
+        let found = match path {
+            "/tree_contents" => self.trees.get(&handle).map(|entries| {
+                let handles = empty_string_or_vec(entries.iter().map(|h| h.to_hex()).collect());
+                serde_json::json!({ "handles": handles })
+            }),
+            "/description" => self
+                .descriptions
+                .get(&handle)
+                .map(|d| serde_json::json!({ "description": d })),
+            "/relation" => {
+                let op = params
+                    .get("op")
+                    .and_then(|o| o.parse::<u8>().ok())
+                    .and_then(|o| Operation::try_from(o).ok());
+                let Some(op) = op else {
+                    return ("400 Bad Request", "missing or invalid op".to_owned());
+                };
+                self.relations
+                    .get(&(handle.clone(), op))
+                    .map(|rhs| relation(&handle, op, rhs))
+            }
+            "/explanations" => {
+                let relations = self
+                    .relations
+                    .iter()
+                    .filter(|(_, rhs)| **rhs == handle)
+                    .map(|((lhs, op), rhs)| relation(lhs, *op, rhs))
+                    .collect();
+                Some(serde_json::json!({
+                    "target": handle.to_hex(),
+                    "relations": empty_string_or_vec(relations),
+                }))
+            }
+            // Blob contents as `Request::BlobContents` assumes the real server
+            // serves them, which has not been checked.
+            "/blob_contents" => {
+                let number = |name| params.get(name).and_then(|n| n.parse::<usize>().ok());
+                let (Some(offset), Some(length)) = (number("offset"), number("length")) else {
+                    return ("400 Bad Request", "missing or invalid range".to_owned());
+                };
+                self.blobs.get(&handle).map(|blob| {
+                    let start = offset.min(blob.len());
+                    let end = offset.saturating_add(length).min(blob.len());
+                    let hex: String = blob[start..end]
+                        .iter()
+                        .map(|b| format!("{b:02x}"))
+                        .collect();
+                    serde_json::json!({ "contents": hex })
+                })
+            }
+            // The pin and tag listing as `Request::PinsAndTags` assumes the
+            // real server serves it, which has not been checked.
+            "/pins_and_tags" => {
+                let pins = self
+                    .pins
+                    .get(&handle)
+                    .map(Vec::as_slice)
+                    .unwrap_or_default();
+                let handles = empty_string_or_vec(pins.iter().map(|h| h.to_hex()).collect());
+                Some(serde_json::json!({ "handles": handles }))
+            }
+            _ => return ("404 Not Found", format!("no endpoint {path}")),
+        };
+        match found {
+            Some(json) => ("200 OK", json.to_string()),
+            None => ("500 Internal Server Error", "unknown handle".to_owned()),
+        }
+    }
+}
+
+fn relation(lhs: &Handle, op: Operation, rhs: &Handle) -> serde_json::Value {
+    serde_json::json!({
+        "op": (op as u8).to_string(),
+        "lhs": lhs.to_hex(),
+        "rhs": rhs.to_hex(),
+    })
+}
+
+/// Boost property trees serialize empty arrays as empty strings.
+fn empty_string_or_vec<T: Into<serde_json::Value>>(values: Vec<T>) -> serde_json::Value {
+    if values.is_empty() {
+        serde_json::json!("")
+    } else {
+        values.into()
+    }
+}
@@ -0,0 +1,370 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::Mutex,
+};
+
+use anyhow::{Context, Result};
+use egui::Ui;
+
+use crate::files;
+
+use super::{ErrorKind, Request};
+
+/// A request with the raw response it got, as one line of a session file.
+#[derive(serde::Deserialize, serde::Serialize, Clone, Debug, PartialEq)]
+pub(crate) struct SessionEntry {
+    /// The request as typed in the console.
+    pub request: String,
+    pub status: Option<u16>,
+    pub body: Option<String>,
+    /// Set when no response was received.
+    pub error: Option<ErrorKind>,
+}
+
+/// Records responses from the server, or answers requests from a recording
+/// instead of the server.
+#[derive(Default)]
+pub(crate) struct Session {
+    mode: Mutex<Mode>,
+}
+
+#[derive(Default)]
+enum Mode {
+    #[default]
+    Off,
+    Record(Recording),
+    /// Responses for each request, in the order they were recorded.
+    Replay(HashMap<String, VecDeque<SessionEntry>>),
+}
+
+/// Where recorded responses go. Natively each one is appended to the file
+/// as it arrives, so nothing is lost if the viewer closes, while on the web
+/// they are kept until the file is downloaded.
+struct Recording {
+    #[cfg(not(target_arch = "wasm32"))]
+    file: std::fs::File,
+    #[cfg(target_arch = "wasm32")]
+    jsonl: String,
+    count: usize,
+    /// The last error writing to the file.
+    error: Option<String>,
+}
+
+/// Controls for recording and replaying sessions.
+pub(crate) struct SessionPanel {
+    file_name: String,
+    status: String,
+}
+
+impl SessionEntry {
+    fn new(request: &Request, response: &Result<(u16, String), ErrorKind>) -> Self {
+        let (status, body, error) = match response {
+            Ok((status, body)) => (Some(*status), Some(body.clone()), None),
+            Err(e) => (None, None, Some(e.clone())),
+        };
+        SessionEntry {
+            request: request.to_cli(),
+            status,
+            body,
+            error,
+        }
+    }
+
+    fn response(&self) -> Result<(u16, String), ErrorKind> {
+        match (&self.error, self.status, &self.body) {
+            (Some(error), ..) => Err(error.clone()),
+            (None, Some(status), Some(body)) => Ok((status, body.clone())),
+            _ => Err(ErrorKind::Connection(
+                "recorded response is missing a status or body".to_owned(),
+            )),
+        }
+    }
+}
+
+impl Session {
+    /// Appends a response to the recording, if recording.
+    pub(crate) fn record(&self, request: &Request, response: &Result<(u16, String), ErrorKind>) {
+        if let Mode::Record(recording) = &mut *self.mode.lock().unwrap() {
+            recording.append(&SessionEntry::new(request, response));
+        }
+    }
+
+    /// Answers a request from the recording, if replaying. Requests sent
+    /// more often than they were recorded get the last recorded response.
+    pub(crate) fn replay(&self, request: &Request) -> Option<Result<(u16, String), ErrorKind>> {
+        let Mode::Replay(entries) = &mut *self.mode.lock().unwrap() else {
+            return None;
+        };
+        let cli = request.to_cli();
+        let Some(responses) = entries.get_mut(&cli) else {
+            return Some(Err(ErrorKind::Connection(format!(
+                "no recorded response for {cli}"
+            ))));
+        };
+        let entry = if responses.len() > 1 {
+            responses.pop_front()
+        } else {
+            responses.front().cloned()
+        };
+        entry.map(|e| e.response())
+    }
+
+    /// Starts appending responses to the file at `path`, after the
+    /// responses already in it.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) fn start_recording(&self, path: &str) -> Result<()> {
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .with_context(|| format!("opening {path}"))?;
+        *self.mode.lock().unwrap() = Mode::Record(Recording {
+            file,
+            count: 0,
+            error: None,
+        });
+        Ok(())
+    }
+
+    /// Starts keeping responses until they are saved with [`Self::to_jsonl`].
+    #[cfg(target_arch = "wasm32")]
+    pub(crate) fn start_recording(&self) {
+        *self.mode.lock().unwrap() = Mode::Record(Recording {
+            jsonl: String::new(),
+            count: 0,
+            error: None,
+        });
+    }
+
+    /// Replays the session in `jsonl`, returning how many responses it has.
+    pub(crate) fn start_replay(&self, jsonl: &str) -> Result<usize> {
+        let mut entries: HashMap<String, VecDeque<SessionEntry>> = HashMap::new();
+        let mut count = 0;
+        for (i, line) in jsonl.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let entry: SessionEntry =
+                serde_json::from_str(line).with_context(|| format!("on line {}", i + 1))?;
+            entries
+                .entry(entry.request.clone())
+                .or_default()
+                .push_back(entry);
+            count += 1;
+        }
+        *self.mode.lock().unwrap() = Mode::Replay(entries);
+        Ok(count)
+    }
+
+    pub(crate) fn stop(&self) {
+        *self.mode.lock().unwrap() = Mode::Off;
+    }
+
+    /// The recording so far as JSONL, if recording.
+    #[cfg(target_arch = "wasm32")]
+    pub(crate) fn to_jsonl(&self) -> Option<String> {
+        match &*self.mode.lock().unwrap() {
+            Mode::Record(recording) => Some(recording.jsonl.clone()),
+            _ => None,
+        }
+    }
+}
+
+impl Recording {
+    fn append(&mut self, entry: &SessionEntry) {
+        let mut line = match serde_json::to_string(entry) {
+            Ok(line) => line,
+            Err(e) => {
+                self.error = Some(format!("serializing {}: {e}", entry.request));
+                return;
+            }
+        };
+        line.push('\n');
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            use std::io::Write;
+
+            if let Err(e) = self.file.write_all(line.as_bytes()) {
+                self.error = Some(format!("writing {}: {e}", entry.request));
+                return;
+            }
+        }
+        #[cfg(target_arch = "wasm32")]
+        self.jsonl.push_str(&line);
+        self.count += 1;
+    }
+}
+
+impl Default for SessionPanel {
+    fn default() -> Self {
+        Self {
+            file_name: "requests.jsonl".to_owned(),
+            status: String::new(),
+        }
+    }
+}
+
+impl SessionPanel {
+    pub(crate) fn ui(&mut self, ui: &mut Ui, session: &Session) {
+        ui.heading("Session");
+        let (recording, replaying) = match &*session.mode.lock().unwrap() {
+            Mode::Off => (None, false),
+            Mode::Record(recording) => (Some((recording.count, recording.error.clone())), false),
+            Mode::Replay(_) => (None, true),
+        };
+        ui.horizontal(|ui| {
+            ui.label("File:");
+            ui.text_edit_singleline(&mut self.file_name);
+        });
+        ui.horizontal(|ui| {
+            #[cfg(target_arch = "wasm32")]
+            if let Some((count, _)) = &recording {
+                if ui.button("save").clicked() {
+                    let result = session
+                        .to_jsonl()
+                        .context("not recording")
+                        .and_then(|jsonl| files::save(&self.file_name, &jsonl));
+                    self.status = match result {
+                        Ok(()) => format!("saved {count} responses"),
+                        Err(e) => format!("{e:#}"),
+                    };
+                }
+            }
+            #[cfg(not(target_arch = "wasm32"))]
+            if let Some((count, error)) = &recording {
+                self.status = match error {
+                    Some(error) => error.clone(),
+                    None => format!("recorded {count} responses"),
+                };
+            }
+            if recording.is_none() && ui.button("record").on_hover_text(RECORD_HINT).clicked() {
+                #[cfg(not(target_arch = "wasm32"))]
+                if let Err(e) = session.start_recording(&self.file_name) {
+                    self.status = format!("{e:#}");
+                    return;
+                }
+                #[cfg(target_arch = "wasm32")]
+                session.start_recording();
+                self.status.clear();
+            }
+            #[cfg(not(target_arch = "wasm32"))]
+            if !replaying
+                && ui
+                    .button("replay")
+                    .on_hover_text("Answer requests from the file instead of the server")
+                    .clicked()
+            {
+                let result = files::read(&self.file_name).and_then(|s| session.start_replay(&s));
+                self.set_replay_status(result);
+            }
+            if (recording.is_some() || replaying) && ui.button("stop").clicked() {
+                session.stop();
+                self.status.clear();
+            }
+        });
+        ui.small("Drop a session file onto the window to replay it.");
+
+        let dropped = ui.ctx().input(|i| i.raw.dropped_files.clone());
+        for file in dropped.iter().filter(|f| is_session_file(f)) {
+            let result = files::read_dropped(file).and_then(|s| session.start_replay(&s));
+            self.set_replay_status(result);
+        }
+        if replaying && self.status.is_empty() {
+            self.status = "replaying".to_owned();
+        }
+        if !self.status.is_empty() {
+            ui.small(&self.status);
+        }
+    }
+
+    fn set_replay_status(&mut self, result: Result<usize>) {
+        self.status = match result {
+            Ok(count) => format!("replaying {count} responses"),
+            Err(e) => format!("{e:#}"),
+        };
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+const RECORD_HINT: &str = "Append every response to the file as it arrives";
+#[cfg(target_arch = "wasm32")]
+const RECORD_HINT: &str = "Keep every response to save it to the file";
+
+/// Whether a dropped file is a recorded session rather than a snapshot.
+pub(crate) fn is_session_file(file: &egui::DroppedFile) -> bool {
+    file.name.ends_with(".jsonl")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphs::{Relation, RelationRhs};
+
+    const SESSION: &str = include_str!("fixtures/session.jsonl");
+
+    fn replay(session: &Session, command: &str) -> Result<Vec<Relation>, ErrorKind> {
+        let request = Request::from_cli(command).unwrap();
+        let (status, body) = session.replay(&request).unwrap()?;
+        request.parse(status, &body)
+    }
+
+    #[test]
+    fn round_trips() {
+        let path = std::env::temp_dir().join(format!("fix-session-{}.jsonl", std::process::id()));
+        let path = path.to_str().unwrap();
+        let _ = std::fs::remove_file(path);
+        let session = Session::default();
+        let request = Request::from_cli(&format!("description {}", "11".repeat(32))).unwrap();
+        session.start_recording(path).unwrap();
+        session.record(&request, &Ok((200, r#"{"description":"x"}"#.to_owned())));
+        // Each response is in the file as soon as it is recorded, and
+        // recording again appends to it.
+        assert_eq!(files::read(path).unwrap().lines().count(), 1);
+        session.start_recording(path).unwrap();
+        session.record(&request, &Err(ErrorKind::Timeout));
+        let jsonl = files::read(path).unwrap();
+        std::fs::remove_file(path).unwrap();
+        assert_eq!(session.start_replay(&jsonl).unwrap(), 2);
+        assert_eq!(
+            session.replay(&request).unwrap().unwrap().1,
+            r#"{"description":"x"}"#
+        );
+        // The last response is repeated.
+        for _ in 0..2 {
+            let error = session.replay(&request).unwrap().unwrap_err();
+            assert_eq!(error, ErrorKind::Timeout);
+        }
+    }
+
+    #[test]
+    fn parses_recorded_responses() {
+        let session = Session::default();
+        session.start_replay(SESSION).unwrap();
+        let tree = "0100000000000000000000000000000000000000000000000000000000000040";
+        let thunk = "02000000000000000000000000000000000000000000000000000000000000c0";
+        let value = "1000000000000000000000000000000000000000000000000000000000000024";
+
+        let contents = replay(&session, &format!("contents {tree}")).unwrap();
+        assert_eq!(contents.len(), 2);
+        assert!(matches!(&contents[1].rhs, RelationRhs::TreeEntry(h, 1) if h.to_hex() == value));
+
+        let eval = replay(&session, &format!("relations {thunk} eval")).unwrap();
+        assert!(matches!(&eval[0].rhs, RelationRhs::Eval(h) if h.to_hex() == value));
+
+        // Boost serializes empty arrays as empty strings.
+        assert!(replay(&session, &format!("explanations {value}"))
+            .unwrap()
+            .is_empty());
+        assert_eq!(
+            replay(&session, &format!("description {tree}")).unwrap_err(),
+            ErrorKind::Status {
+                status: 404,
+                body: "no description".to_owned()
+            }
+        );
+        assert!(matches!(
+            replay(&session, &format!("pins {tree}")),
+            Err(ErrorKind::Connection(_))
+        ));
+    }
+}
@@ -0,0 +1,340 @@
+use std::{
+    sync::{atomic::AtomicUsize, mpsc, Arc},
+    time::Duration,
+};
+
+use reqwest::Client;
+
+use crate::{
+    graphs::{Relation, RelationRhs},
+    handle::{test_handle, Handle, Operation},
+};
+
+use super::{
+    mock::MockGraph, tag_requests, ErrorKind, HttpContext, HttpError, HttpLog, HttpMessage,
+    LogLine, Request, RequestCache, Session,
+};
+
+fn handle(i: u8, metadata: u8) -> Handle {
+    let mut handle = test_handle(i);
+    handle.content[31] = metadata;
+    handle
+}
+
+fn tree(i: u8) -> Handle {
+    handle(i, 0x40)
+}
+
+fn thunk(i: u8) -> Handle {
+    handle(i, 0xc0)
+}
+
+fn literal(i: u8) -> Handle {
+    handle(i, 0x21)
+}
+
+fn tag(i: u8) -> Handle {
+    handle(i, 0x80)
+}
+
+fn blob(i: u8) -> Handle {
+    handle(i, 0x00)
+}
+
+fn graph() -> MockGraph {
+    let mut graph = MockGraph::default();
+    graph.trees.insert(tree(1), vec![thunk(2), literal(3)]);
+    graph.trees.insert(tree(4), vec![]);
+    graph
+        .descriptions
+        .insert(tree(1), "a tree with a thunk".to_owned());
+    graph
+        .relations
+        .insert((thunk(2), Operation::Eval), literal(3));
+    graph
+        .relations
+        .insert((tree(1), Operation::Apply), literal(3));
+    graph
+        .trees
+        .insert(tag(5), vec![tree(1), literal(6), literal(3)]);
+    graph.pins.insert(tree(1), vec![tag(5), thunk(2)]);
+    graph.blobs.insert(blob(7), (0..=255).collect());
+    graph
+}
+
+fn context(tx: mpsc::Sender<HttpMessage>) -> HttpContext {
+    HttpContext {
+        client: Arc::new(Client::new()),
+        egui_ctx: egui::Context::default(),
+        url_base: String::new(),
+        tx,
+        counter: Arc::new(AtomicUsize::new(0)),
+        cache: Arc::new(RequestCache::default()),
+        session: Arc::new(Session::default()),
+        offline: false,
+    }
+}
+
+/// Sends a request to a server for `graph` and waits for its result.
+async fn send(graph: &MockGraph, request: Request) -> Result<Vec<Relation>, HttpError> {
+    let (tx, rx) = mpsc::channel();
+    let ctx = HttpContext {
+        url_base: graph.clone().serve().await,
+        ..context(tx)
+    };
+    let id = request.send(ctx);
+    receive(rx, id).await
+}
+
+async fn receive(rx: mpsc::Receiver<HttpMessage>, id: usize) -> Result<Vec<Relation>, HttpError> {
+    loop {
+        match rx.try_recv() {
+            Ok(HttpMessage::Received(i, result, _)) if i == id => return result,
+            Ok(_) => {}
+            Err(mpsc::TryRecvError::Empty) => tokio::time::sleep(Duration::from_millis(5)).await,
+            Err(mpsc::TryRecvError::Disconnected) => panic!("no response"),
+        }
+    }
+}
+
+#[tokio::test]
+async fn tree_contents() {
+    let relations = send(&graph(), Request::Contents(tree(1))).await.unwrap();
+    assert_eq!(
+        relations,
+        vec![
+            Relation::new(tree(1), RelationRhs::TreeEntry(thunk(2), 0)),
+            Relation::new(tree(1), RelationRhs::TreeEntry(literal(3), 1)),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn empty_tree_contents() {
+    let relations = send(&graph(), Request::Contents(tree(4))).await.unwrap();
+    assert!(relations.is_empty());
+}
+
+#[tokio::test]
+async fn blob_contents() {
+    let relations = send(&graph(), Request::BlobContents(blob(7), 250, 8)).await;
+    // The blob ends before the length requested.
+    assert_eq!(
+        relations.unwrap(),
+        vec![Relation::new(
+            blob(7),
+            RelationRhs::BlobChunk(250, vec![250, 251, 252, 253, 254, 255])
+        )]
+    );
+    let relations = send(&graph(), Request::BlobContents(blob(7), 16, 2)).await;
+    assert_eq!(
+        relations.unwrap(),
+        vec![Relation::new(
+            blob(7),
+            RelationRhs::BlobChunk(16, vec![16, 17])
+        )]
+    );
+}
+
+#[tokio::test]
+async fn description() {
+    let relations = send(&graph(), Request::Description(tree(1))).await.unwrap();
+    assert_eq!(
+        relations,
+        vec![Relation::new(
+            tree(1),
+            RelationRhs::Description("a tree with a thunk".to_owned())
+        )]
+    );
+}
+
+#[tokio::test]
+async fn relations() {
+    let eval = send(&graph(), Request::Relations(thunk(2), Operation::Eval)).await;
+    assert_eq!(
+        eval.unwrap(),
+        vec![Relation::new(thunk(2), RelationRhs::Eval(literal(3)))]
+    );
+    let apply = send(&graph(), Request::Relations(tree(1), Operation::Apply)).await;
+    assert_eq!(
+        apply.unwrap(),
+        vec![Relation::new(tree(1), RelationRhs::Apply(literal(3)))]
+    );
+}
+
+#[tokio::test]
+async fn explanations() {
+    let mut relations = send(&graph(), Request::Explanations(literal(3)))
+        .await
+        .unwrap();
+    relations.sort_by_key(|r| r.lhs.clone());
+    assert_eq!(
+        relations,
+        vec![
+            Relation::new(tree(1), RelationRhs::Apply(literal(3))),
+            Relation::new(thunk(2), RelationRhs::Eval(literal(3))),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn empty_explanations() {
+    let relations = send(&graph(), Request::Explanations(tree(4))).await;
+    assert!(relations.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn pins_and_tags() {
+    let relations = send(&graph(), Request::PinsAndTags(tree(1))).await;
+    assert_eq!(
+        relations.unwrap(),
+        vec![
+            Relation::new(tag(5), RelationRhs::Pin(tree(1))),
+            Relation::new(thunk(2), RelationRhs::Pin(tree(1))),
+        ]
+    );
+    let relations = send(&graph(), Request::PinsAndTags(tree(4))).await;
+    assert!(relations.unwrap().is_empty());
+}
+
+#[test]
+fn requests_pinning_tags() {
+    let relations = [
+        Relation::new(tag(5), RelationRhs::Pin(tree(1))),
+        Relation::new(thunk(2), RelationRhs::Pin(tree(1))),
+        Relation::new(tag(5), RelationRhs::TagTarget(tree(1))),
+    ];
+    assert_eq!(tag_requests(&relations), [Request::TagContents(tag(5))]);
+}
+
+#[tokio::test]
+async fn tag_contents() {
+    let relations = send(&graph(), Request::TagContents(tag(5))).await;
+    assert_eq!(
+        relations.unwrap(),
+        vec![
+            Relation::new(tag(5), RelationRhs::TagTarget(tree(1))),
+            Relation::new(tag(5), RelationRhs::TagAuthor(literal(6))),
+            Relation::new(tag(5), RelationRhs::TagLabel(literal(3))),
+        ]
+    );
+    let error = send(&graph(), Request::TagContents(tree(4)))
+        .await
+        .unwrap_err();
+    assert_eq!(
+        error.kind,
+        ErrorKind::Json("expected tag to have children".to_owned())
+    );
+}
+
+#[tokio::test]
+async fn error_responses() {
+    let error = send(&graph(), Request::Description(tree(4)))
+        .await
+        .unwrap_err();
+    assert_eq!(
+        error.kind,
+        ErrorKind::Status {
+            status: 500,
+            body: "unknown handle".to_owned()
+        }
+    );
+    assert_eq!(
+        error.to_string(),
+        "request #0: server responded 500 Internal Server Error: unknown handle"
+    );
+    let error = send(&graph(), Request::Relations(literal(3), Operation::Eval))
+        .await
+        .unwrap_err();
+    assert!(matches!(error.kind, ErrorKind::Status { status: 500, .. }));
+    let error = send(&graph(), Request::Contents(tree(9)))
+        .await
+        .unwrap_err();
+    assert_eq!(
+        error.kind,
+        ErrorKind::Status {
+            status: 500,
+            body: "unknown handle".to_owned()
+        }
+    );
+}
+
+#[tokio::test]
+async fn schema_mismatch() {
+    // Tags have three children, which this tree does not.
+    let error = send(&graph(), Request::TagContents(tree(1)))
+        .await
+        .unwrap_err();
+    assert!(matches!(error.kind, ErrorKind::Json(_)));
+}
+
+#[tokio::test]
+async fn connection_failure() {
+    let (tx, rx) = mpsc::channel();
+    let ctx = HttpContext {
+        url_base: "http://127.0.0.1:1".to_owned(),
+        ..context(tx)
+    };
+    let id = Request::Contents(tree(1)).send(ctx);
+    let error = receive(rx, id).await.unwrap_err();
+    assert_eq!(error.id, id);
+    assert!(matches!(error.kind, ErrorKind::Connection(_)));
+}
+
+#[tokio::test]
+async fn logs_requests() {
+    let mut log = HttpLog::new();
+    let ctx = HttpContext {
+        url_base: graph().serve().await,
+        ..context(log.tx.clone())
+    };
+    let id = Request::Description(tree(4)).send(ctx.clone());
+    let merged = Request::Description(tree(4)).send(ctx.clone());
+    let invalid = Request::parse_send("contents nothing".to_owned(), ctx);
+    let mut received = 0;
+    while received < 3 {
+        match log.rx.try_recv() {
+            Ok(message) => {
+                received += matches!(message, HttpMessage::Received(..)) as usize;
+                log.on_message(&message);
+            }
+            Err(_) => tokio::time::sleep(Duration::from_millis(5)).await,
+        }
+    }
+    let entry_for = |id| {
+        log.log
+            .iter()
+            .find_map(|line| match line {
+                LogLine::Request(entry) if entry.id == id => Some(entry),
+                _ => None,
+            })
+            .unwrap()
+    };
+    let entry = entry_for(id);
+    assert_eq!(entry.request, Some(Request::Description(tree(4))));
+    assert_eq!(entry.status, Some(500));
+    assert!(matches!(entry.result, Some(Err(_))));
+    assert!(entry.completed.unwrap() >= entry.sent);
+    let entry = entry_for(merged);
+    assert_eq!(entry.merged_into, Some(id));
+    assert!(matches!(&entry.result, Some(Err(e)) if e.id == merged));
+    let entry = entry_for(invalid);
+    assert_eq!(entry.request, None);
+    assert!(matches!(
+        entry.result,
+        Some(Err(HttpError {
+            kind: ErrorKind::Command(_),
+            ..
+        }))
+    ));
+}
+
+#[test]
+fn caps_the_log() {
+    let mut log = HttpLog::new();
+    for i in 0..600 {
+        log.print(i.to_string());
+    }
+    assert_eq!(log.log.len(), 500);
+    assert!(matches!(&log.log[0], LogLine::Text(text) if text == "100"));
+}
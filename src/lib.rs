@@ -1,6 +1,13 @@
 mod app;
+mod bookmarks;
+mod clock;
+mod console;
+mod crawler;
+mod files;
 mod graphs;
 mod handle;
 mod http;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod query;
 
-pub use app::App;
+pub use app::{App, LaunchOptions};
@@ -8,6 +8,18 @@ fn main() -> eframe::Result<()> {
 
     env_logger::init(); // Log to stderr (if you run with `RUST_LOG=debug`).
 
+    let options = match fix_viewer::LaunchOptions::from_args(std::env::args().skip(1)) {
+        Ok(Some(options)) => options,
+        Ok(None) => {
+            println!("{}", fix_viewer::LaunchOptions::USAGE);
+            return Ok(());
+        }
+        Err(e) => {
+            eprintln!("error: {e:#}\n\n{}", fix_viewer::LaunchOptions::USAGE);
+            std::process::exit(2);
+        }
+    };
+
     // Initialize a tokio runtime.
     let rt = Runtime::new().unwrap();
     let native_options = eframe::NativeOptions::default();
@@ -15,7 +27,7 @@ fn main() -> eframe::Result<()> {
         eframe::run_native(
             "Fix Viewer",
             native_options,
-            Box::new(|cc| Box::new(fix_viewer::App::new(cc))),
+            Box::new(|cc| Box::new(fix_viewer::App::new(cc, options))),
         )
     })
 }
@@ -33,7 +45,13 @@ fn main() {
             .start(
                 "the_canvas_id", // hardcode it
                 web_options,
-                Box::new(|cc| Box::new(fix_viewer::App::new(cc))),
+                // The url fragment is read by the app, which keeps it in sync.
+                Box::new(|cc| {
+                    Box::new(fix_viewer::App::new(
+                        cc,
+                        fix_viewer::LaunchOptions::default(),
+                    ))
+                }),
             )
             .await
             .expect("failed to start eframe");
@@ -0,0 +1,320 @@
+//! `fix-query`, which runs console commands against a server from a shell,
+//! without opening the viewer.
+
+use std::{
+    io::{self, BufRead, Write},
+    process::ExitCode,
+};
+
+use anyhow::{bail, Context, Result};
+use reqwest::Client;
+use tokio::runtime::Runtime;
+
+use crate::{
+    bookmarks::{self, Bookmarks},
+    console::script::{Host, Runner},
+    graphs::{export, Relation, RelationStorage},
+    http::{self, ErrorKind, HttpError, Request},
+};
+
+const DEFAULT_SERVER: &str = "http://127.0.0.1:9090";
+
+const USAGE: &str = "\
+Usage: fix-query [--server <url>] [--bookmarks <file>] [--json] [command...]
+
+Runs a console command, such as `fix-query relations <handle> eval`, and
+prints the relations it returned. Without a command, reads commands from
+standard input until it ends or `exit` is typed.
+
+Options:
+  --server <url>      The server to send requests to [default: http://127.0.0.1:9090]
+  --bookmarks <file>  Read bookmarks exported from the viewer, so that @name
+                      stands for the handle bookmarked as name
+  --json              Print one JSON object per relation instead of text
+  -h, --help          Print this message
+
+Type `help` as a command to list the commands.";
+
+struct Options {
+    url_base: String,
+    json: bool,
+    /// The path to a bookmarks file.
+    bookmarks: Option<String>,
+    /// The command to run, or nothing to read commands from standard input.
+    command: Option<String>,
+}
+
+/// Sends commands to a server and prints what they return.
+struct Query {
+    runtime: Runtime,
+    client: Client,
+    url_base: String,
+    json: bool,
+    bookmarks: Bookmarks,
+    /// The relations returned so far, which shortened handles are resolved
+    /// against.
+    known: RelationStorage,
+    /// Runs scripts the same way as the viewer's console.
+    runner: Runner,
+}
+
+/// Sends the runner's requests one at a time, printing relations to `out`
+/// and errors to standard error.
+struct Terminal<'a, W> {
+    runtime: &'a Runtime,
+    client: &'a Client,
+    url_base: &'a str,
+    json: bool,
+    out: &'a mut W,
+    /// The results of the requests sent, to pass to the runner.
+    received: Vec<(usize, Result<Vec<Relation>, HttpError>)>,
+    next_id: usize,
+    failed: bool,
+}
+
+/// Runs `fix-query` with the arguments after the program name.
+pub fn main(args: impl IntoIterator<Item = String>) -> ExitCode {
+    let result = parse_args(args).and_then(|options| {
+        let Some(options) = options else {
+            println!("{USAGE}");
+            return Ok(true);
+        };
+        let mut query = Query::new(options.url_base, options.json)?;
+        if let Some(path) = &options.bookmarks {
+            query.bookmarks = bookmarks::read(path)?;
+        }
+        let mut out = io::stdout().lock();
+        match options.command {
+            Some(command) => Ok(query.run(&command, &mut out)),
+            None => query.repl(io::stdin().lock(), &mut out),
+        }
+    });
+    match result {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::FAILURE,
+        Err(e) => {
+            eprintln!("error: {e:#}");
+            ExitCode::from(2)
+        }
+    }
+}
+
+/// Parses the arguments, or returns `None` if help was asked for.
+fn parse_args(args: impl IntoIterator<Item = String>) -> Result<Option<Options>> {
+    let mut options = Options {
+        url_base: DEFAULT_SERVER.to_owned(),
+        json: false,
+        bookmarks: None,
+        command: None,
+    };
+    let mut args = args.into_iter();
+    let mut command = vec![];
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" if command.is_empty() => return Ok(None),
+            "--json" if command.is_empty() => options.json = true,
+            "--server" if command.is_empty() => {
+                let url = args.next().context("--server expects a url")?;
+                options.url_base = url.trim_end_matches('/').to_owned();
+            }
+            "--bookmarks" if command.is_empty() => {
+                let path = args.next().context("--bookmarks expects a file")?;
+                options.bookmarks = Some(path);
+            }
+            option if command.is_empty() && option.starts_with('-') => {
+                bail!("unknown option {option}\n\n{USAGE}")
+            }
+            _ => command.push(arg),
+        }
+    }
+    if !command.is_empty() {
+        options.command = Some(command.join(" "));
+    }
+    Ok(Some(options))
+}
+
+impl Query {
+    fn new(url_base: String, json: bool) -> Result<Self> {
+        Ok(Self {
+            runtime: Runtime::new().context("starting the tokio runtime")?,
+            client: http::client(),
+            url_base,
+            json,
+            bookmarks: Bookmarks::default(),
+            known: RelationStorage::default(),
+            runner: Runner::default(),
+        })
+    }
+
+    /// Runs commands from `input` line by line, returning whether they all
+    /// succeeded.
+    fn repl(&mut self, input: impl BufRead, out: &mut impl Write) -> Result<bool> {
+        let mut succeeded = true;
+        let mut lines = input.lines();
+        loop {
+            eprint!("> ");
+            let Some(line) = lines.next() else {
+                break;
+            };
+            let line = line.context("reading standard input")?;
+            if matches!(line.trim(), "exit" | "quit") {
+                break;
+            }
+            succeeded &= self.run(&line, out);
+        }
+        Ok(succeeded)
+    }
+
+    /// Runs console input, which can hold several commands and pipelines,
+    /// returning whether every command succeeded. Failures are printed to
+    /// standard error, and stop the pipeline they are in.
+    fn run(&mut self, input: &str, out: &mut impl Write) -> bool {
+        self.runner.run(input);
+        let mut terminal = Terminal {
+            runtime: &self.runtime,
+            client: &self.client,
+            url_base: &self.url_base,
+            json: self.json,
+            out,
+            received: vec![],
+            next_id: 0,
+            failed: false,
+        };
+        loop {
+            self.runner
+                .pump(&mut terminal, &self.bookmarks, &self.known);
+            // Requests are answered as they are sent, so nothing is left to
+            // run once nothing was sent.
+            if terminal.received.is_empty() {
+                break;
+            }
+            for (id, result) in std::mem::take(&mut terminal.received) {
+                self.runner.on_received(id, &result);
+                for relation in result.into_iter().flatten() {
+                    self.known.insert(relation);
+                }
+            }
+        }
+        !terminal.failed
+    }
+}
+
+impl<W: Write> Host for Terminal<'_, W> {
+    fn send(&mut self, command: String) -> usize {
+        let id = self.next_id;
+        self.next_id += 1;
+        let result = match Request::from_cli(&command) {
+            Ok(request) => self
+                .runtime
+                .block_on(request.get(self.client, self.url_base)),
+            Err(e) => Err(ErrorKind::Command(format!("{e:#}"))),
+        };
+        match &result {
+            Ok(relations) => {
+                if let Err(e) = self.print_relations(relations) {
+                    self.error(&command, format!("writing output: {e}"));
+                }
+            }
+            Err(e) => self.error(&command, e.to_string()),
+        }
+        self.received
+            .push((id, result.map_err(|kind| HttpError { id, kind })));
+        id
+    }
+
+    fn print(&mut self, _command: &str, text: String) {
+        let _ = writeln!(self.out, "{text}");
+    }
+
+    fn error(&mut self, command: &str, error: String) {
+        eprintln!("error: {command}: {error}");
+        self.failed = true;
+    }
+}
+
+impl<W: Write> Terminal<'_, W> {
+    fn print_relations(&mut self, relations: &[Relation]) -> io::Result<()> {
+        for relation in relations {
+            if self.json {
+                writeln!(self.out, "{}", export::json_line(relation))?;
+            } else {
+                writeln!(self.out, "{} {}", relation.lhs.to_hex(), relation.rhs)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        handle::{test_handle, Handle, Operation},
+        http::mock::MockGraph,
+    };
+
+    fn handle(i: u8) -> Handle {
+        let mut handle = test_handle(i);
+        // Thunks, which can be evaluated.
+        handle.content[31] = 0xc0;
+        handle
+    }
+
+    fn args(args: &str) -> Vec<String> {
+        args.split_whitespace().map(str::to_owned).collect()
+    }
+
+    #[test]
+    fn parses_args() {
+        let options = parse_args(args(
+            "--server http://fix:80/ --json --bookmarks team.bookmarks.json eval ab",
+        ))
+        .unwrap()
+        .unwrap();
+        assert_eq!(options.url_base, "http://fix:80");
+        assert!(options.json);
+        assert_eq!(options.bookmarks.as_deref(), Some("team.bookmarks.json"));
+        assert_eq!(options.command.as_deref(), Some("eval ab"));
+        let options = parse_args(args("")).unwrap().unwrap();
+        assert_eq!(options.url_base, DEFAULT_SERVER);
+        assert!(options.command.is_none());
+        assert!(parse_args(args("--help")).unwrap().is_none());
+        assert!(parse_args(args("--verbose")).is_err());
+        assert!(parse_args(args("--server")).is_err());
+    }
+
+    #[test]
+    fn runs_pipelines() {
+        let mut graph = MockGraph::default();
+        graph.trees.insert(handle(1), vec![handle(2), handle(3)]);
+        graph
+            .relations
+            .insert((handle(2), Operation::Eval), handle(4));
+        // The server runs on its own runtime, which would otherwise be blocked.
+        let server = Runtime::new().unwrap();
+        let url_base = server.block_on(graph.serve());
+        let mut query = Query::new(url_base, false).unwrap();
+
+        query
+            .bookmarks
+            .insert("pair", handle(1), String::new())
+            .unwrap();
+        let mut out = vec![];
+        assert!(query.run("contents @pair", &mut out));
+        assert_eq!(String::from_utf8(out).unwrap().lines().count(), 2);
+        assert_eq!(query.runner.last(), [handle(2), handle(3)]);
+
+        // The second entry has no result, which fails the pipeline.
+        query.json = true;
+        let mut out = vec![];
+        assert!(!query.run(&format!("contents {} | eval", handle(1).to_hex()), &mut out));
+        let out = String::from_utf8(out).unwrap();
+        let line: serde_json::Value = serde_json::from_str(out.lines().last().unwrap()).unwrap();
+        assert_eq!(line["relation"], "eval");
+
+        // Handles returned before can be shortened.
+        assert!(query.run("eval 0202", &mut vec![]));
+        assert_eq!(query.runner.last(), [handle(4)]);
+        assert!(!query.run("eval 0505", &mut vec![]));
+    }
+}